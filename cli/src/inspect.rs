@@ -0,0 +1,255 @@
+use anchor_lang::Discriminator;
+use anyhow::{Context, Result};
+use serde_json::{json, Value};
+use solana_client::rpc_client::RpcClient;
+use solana_client::rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig};
+use solana_client::rpc_filter::{Memcmp, RpcFilterType};
+use solana_sdk::account::Account;
+use solana_sdk::clock::Clock;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::sysvar;
+use staking_client::{accounts, pda, rewards, StakeAccount, StakingPool, UnstakeAccount};
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+pub fn pool_json(pool: &StakingPool) -> Value {
+    json!({
+        "creator": pool.creator.to_string(),
+        "index": pool.index,
+        "admin": pool.admin.to_string(),
+        "pending_admin": pool.pending_admin.to_string(),
+        "token_mint": pool.token_mint.to_string(),
+        "min_stake_amount": pool.min_stake_amount,
+        "unbonding_seconds": pool.unbonding_seconds,
+        "reward_rate": pool.reward_rate,
+        "reward_algorithm": format!("{:?}", pool.reward_algorithm),
+        "total_stake": pool.total_stake,
+        "total_stake_tokens": pool.total_stake_tokens().ok(),
+        "total_reward": pool.total_reward,
+        "undistributed_reward": pool.undistributed_reward,
+        "last_reward_timestamp": pool.last_reward_timestamp,
+        "reward_per_share": pool.reward_per_share.to_string(),
+        "extension_policy": pool.extension_policy,
+        "slasher": pool.slasher.to_string(),
+        "stake_scale": pool.effective_stake_scale().to_string(),
+        "unbonding_scale": pool.effective_unbonding_scale().to_string(),
+        "total_unbonding": pool.total_unbonding,
+        "total_owed_rewards": pool.total_owed_rewards,
+        "owed_rewards_tracked": pool.owed_rewards_tracked,
+        "version": pool.version,
+        "min_stake_duration": pool.min_stake_duration,
+        "max_loyalty_multiplier": pool.max_loyalty_multiplier,
+        "loyalty_ramp_seconds": pool.loyalty_ramp_seconds,
+        "total_weight": pool.total_weight,
+        "linear_unbonding": pool.linear_unbonding,
+        "allowlist_root": hex(&pool.allowlist_root),
+        "attestor": pool.attestor.to_string(),
+        "reward_schedule": pool
+            .reward_schedule
+            .iter()
+            .map(|change| json!({
+                "effective_timestamp": change.effective_timestamp,
+                "reward_rate": change.reward_rate,
+                "reward_algorithm": format!("{:?}", change.reward_algorithm),
+            }))
+            .collect::<Vec<_>>(),
+        "epoch_length": pool.epoch_length,
+        "pending_weight": pool.pending_weight,
+        "open_epoch": pool.is_epoch_mode().then(|| pool.open_epoch()),
+    })
+}
+
+pub fn stake_account_json(stake_account: &StakeAccount) -> Value {
+    let mut stake_account = stake_account.clone();
+    stake_account.migrate();
+
+    json!({
+        "staking_pool": stake_account.staking_pool.to_string(),
+        "user": stake_account.user.to_string(),
+        "amount": stake_account.amount,
+        "reward": stake_account.reward.to_string(),
+        "reward_debt": stake_account.reward_debt.to_string(),
+        "vesting_amount": stake_account.vesting_amount,
+        "vesting_start_timestamp": stake_account.vesting_start_timestamp,
+        "vesting_cliff_timestamp": stake_account.vesting_cliff_timestamp,
+        "vesting_end_timestamp": stake_account.vesting_end_timestamp,
+        "stake_timestamp": stake_account.stake_timestamp,
+        "loyalty_timestamp": stake_account.loyalty_timestamp,
+        "weight": stake_account.weight,
+        "pending_weight": stake_account.pending_weight,
+        "pending_epoch": stake_account.pending_epoch,
+    })
+}
+
+pub fn unstake_account_json(address: &Pubkey, unstake_account: &UnstakeAccount) -> Value {
+    json!({
+        "address": address.to_string(),
+        "amount": unstake_account.amount,
+        "withdrawable_timestamp": unstake_account.withdrawable_timestamp,
+        "linear": unstake_account.linear,
+        "unstake_timestamp": unstake_account.unstake_timestamp,
+        "withdrawn_amount": unstake_account.withdrawn_amount,
+        "unbonding_scale": unstake_account.unbonding_scale.to_string(),
+    })
+}
+
+fn fetch_pool(rpc: &RpcClient, pool: &Pubkey) -> Result<StakingPool> {
+    let data = rpc
+        .get_account_data(pool)
+        .with_context(|| format!("fetching staking pool {}", pool))?;
+
+    Ok(accounts::deserialize_staking_pool(&data)?)
+}
+
+fn fetch_clock(rpc: &RpcClient) -> Result<Clock> {
+    let data = rpc.get_account_data(&sysvar::clock::ID)?;
+
+    Ok(bincode::deserialize(&data)?)
+}
+
+/// Unclaimed rewards of every stake account of `pool`, along with the
+/// `reward_per_share` they were computed at.
+pub fn owed_rewards(rpc: &RpcClient, pool: &Pubkey) -> Result<(u128, u128)> {
+    let staking_pool = fetch_pool(rpc, pool)?;
+    let stake_accounts: Vec<(Pubkey, Account)> = rpc.get_program_accounts_with_config(
+        &staking_program::ID,
+        RpcProgramAccountsConfig {
+            filters: Some(vec![
+                RpcFilterType::Memcmp(Memcmp::new_base58_encoded(0, &StakeAccount::DISCRIMINATOR)),
+                RpcFilterType::Memcmp(Memcmp::new_base58_encoded(8, &pool.to_bytes())),
+            ]),
+            account_config: RpcAccountInfoConfig {
+                encoding: Some(solana_account_decoder::UiAccountEncoding::Base64),
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+    )?;
+    let stake_accounts = stake_accounts
+        .iter()
+        .map(|(_, account)| accounts::deserialize_stake_account(&account.data))
+        .collect::<anchor_lang::Result<Vec<_>>>()?;
+
+    Ok(rewards::owed_rewards(&staking_pool, &stake_accounts)?)
+}
+
+pub fn show_pool(rpc: &RpcClient, pool: &Pubkey) -> Result<()> {
+    let staking_pool = fetch_pool(rpc, pool)?;
+
+    let mut output = pool_json(&staking_pool);
+    output["address"] = json!(pool.to_string());
+    println!("{}", serde_json::to_string_pretty(&output)?);
+
+    Ok(())
+}
+
+/// Finds `user`'s stake account in `pool`, falling back to the legacy
+/// account opened before positions were scoped by pool.
+pub fn find_stake_account(
+    rpc: &RpcClient,
+    pool: &Pubkey,
+    user: &Pubkey,
+) -> Result<Option<(Pubkey, StakeAccount)>> {
+    Ok(find_stake_accounts(rpc, pool, user)?.into_iter().next())
+}
+
+/// `user`'s stake accounts in `pool`: the pool scoped one first, then the
+/// legacy one until it is relocated.
+pub fn find_stake_accounts(
+    rpc: &RpcClient,
+    pool: &Pubkey,
+    user: &Pubkey,
+) -> Result<Vec<(Pubkey, StakeAccount)>> {
+    let mut stake_accounts = vec![];
+    for (address, _) in [
+        pda::find_stake_account_address(pool, user),
+        pda::find_legacy_stake_account_address(user),
+    ] {
+        let stake_account = rpc
+            .get_account_with_commitment(&address, rpc.commitment())?
+            .value
+            .map(|account| accounts::deserialize_stake_account(&account.data))
+            .transpose()?
+            .filter(|stake_account| stake_account.staking_pool == *pool);
+        if let Some(stake_account) = stake_account {
+            stake_accounts.push((address, stake_account));
+        }
+    }
+
+    Ok(stake_accounts)
+}
+
+pub fn show_position(rpc: &RpcClient, pool: &Pubkey, user: &Pubkey) -> Result<()> {
+    let staking_pool = fetch_pool(rpc, pool)?;
+    let clock = fetch_clock(rpc)?;
+
+    let mut stakes = vec![];
+    for (stake_address, stake_account) in find_stake_accounts(rpc, pool, user)? {
+        let pending_reward = rewards::pending_reward(&staking_pool, &stake_account, &clock)?;
+        let mut value = stake_account_json(&stake_account);
+        value["address"] = json!(stake_address.to_string());
+        value["legacy"] = json!(stake_address != pda::find_stake_account_address(pool, user).0);
+        value["amount_tokens"] = json!(staking_pool.stake_to_tokens(stake_account.amount)?);
+        value["pending_reward"] = json!(pending_reward.to_string());
+        stakes.push(value);
+    }
+
+    let unstake_accounts: Vec<(Pubkey, Account)> = rpc.get_program_accounts_with_config(
+        &staking_program::ID,
+        RpcProgramAccountsConfig {
+            filters: Some(vec![
+                RpcFilterType::Memcmp(Memcmp::new_base58_encoded(
+                    0,
+                    &UnstakeAccount::DISCRIMINATOR,
+                )),
+                RpcFilterType::Memcmp(Memcmp::new_base58_encoded(8, &pool.to_bytes())),
+                RpcFilterType::Memcmp(Memcmp::new_base58_encoded(40, &user.to_bytes())),
+            ]),
+            account_config: RpcAccountInfoConfig {
+                encoding: Some(solana_account_decoder::UiAccountEncoding::Base64),
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+    )?;
+    let mut unstakes = vec![];
+    for (address, account) in unstake_accounts {
+        let unstake_account = accounts::deserialize_unstake_account(&account.data)?;
+        let mut value = unstake_account_json(&address, &unstake_account);
+        value["remaining_amount"] = json!(unstake_account.remaining_amount(&staking_pool)?);
+        value["withdrawable_amount"] = json!(unstake_account.to_tokens(
+            &staking_pool,
+            unstake_account.unlocked_amount(clock.unix_timestamp as u64)
+                - unstake_account.withdrawn_amount,
+        )?);
+        unstakes.push(value);
+    }
+
+    let blocklist = match rpc
+        .get_account_with_commitment(&pda::find_blocklist_address(pool, user).0, rpc.commitment())?
+        .value
+    {
+        Some(account) => {
+            let blocklist = accounts::deserialize_blocklist(&account.data)?;
+            json!({
+                "reason": blocklist.reason,
+                "frozen": blocklist.frozen,
+            })
+        }
+        None => Value::Null,
+    };
+
+    let output = json!({
+        "user": user.to_string(),
+        "staking_pool": pool.to_string(),
+        "timestamp": clock.unix_timestamp,
+        "blocklist": blocklist,
+        "stake_accounts": stakes,
+        "unstake_accounts": unstakes,
+    });
+    println!("{}", serde_json::to_string_pretty(&output)?);
+
+    Ok(())
+}
@@ -0,0 +1,935 @@
+mod inspect;
+mod transaction;
+
+use anyhow::{anyhow, Context, Result};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::hash::Hash;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signature};
+use staking_client::instructions::{self, PoolKeys};
+use staking_client::{
+    accounts, allowlist, attestation, pda, ConfigParams, GrantVestingStakeParams,
+    InitializeStakingPoolParams, RewardAlgorithm, ScheduledRewardChange,
+};
+use staking_program::helper;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use transaction::{SignerArg, TransactionSender};
+
+#[derive(Parser)]
+#[command(version, about = "Operate SVM staking pools")]
+struct Cli {
+    /// JSON RPC endpoint.
+    #[arg(
+        long,
+        short = 'u',
+        global = true,
+        default_value = "https://api.devnet.solana.com"
+    )]
+    url: String,
+
+    /// Keypair file, or a bare pubkey when the authority signs elsewhere.
+    #[arg(
+        long,
+        short = 'k',
+        global = true,
+        default_value = "~/.config/solana/id.json"
+    )]
+    keypair: String,
+
+    /// Keypair file or pubkey paying fees and rent. Defaults to `--keypair`.
+    #[arg(long, global = true)]
+    fee_payer: Option<String>,
+
+    /// Sign with the available keypairs and print the transaction instead of sending it.
+    #[arg(long, global = true)]
+    sign_only: bool,
+
+    /// Recent blockhash to sign with, required to stay fully offline.
+    #[arg(long, global = true)]
+    blockhash: Option<Hash>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Args)]
+struct PoolArgs {
+    /// Staking pool address.
+    #[arg(long)]
+    pool: Pubkey,
+
+    /// Token mint of the pool, read from the pool account when omitted.
+    #[arg(long)]
+    mint: Option<Pubkey>,
+
+    /// Token program owning the mint, read from the mint account when omitted.
+    #[arg(long)]
+    token_program: Option<Pubkey>,
+}
+
+#[derive(Args)]
+struct AttestationArgs {
+    /// Attestor signature, for pools requiring attestations.
+    #[arg(long, requires = "attestation_expiry")]
+    attestation_signature: Option<Signature>,
+
+    /// Expiry the attestation was signed with.
+    #[arg(long, requires = "attestation_signature")]
+    attestation_expiry: Option<u64>,
+
+    /// Attestor of the pool, read from the pool account when omitted.
+    #[arg(long, requires = "attestation_signature")]
+    attestor: Option<Pubkey>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Algorithm {
+    PerTokenPerSecond,
+    TotalPerSecond,
+    /// Total per second, halving every `--half-life-seconds`.
+    Decaying,
+    /// `--apr-bps` a year on the staked tokens.
+    TargetApr,
+    /// An APR falling from `--max-apr-bps` as the pool fills up to
+    /// `--target-stake`, with a kink at `--kink-utilization-bps`.
+    UtilizationApr,
+}
+
+/// Parameters of the reward algorithms that take some.
+#[derive(Args)]
+struct AlgorithmArgs {
+    /// Unix timestamp a decaying emission starts halving from.
+    #[arg(long)]
+    decay_anchor_timestamp: Option<u64>,
+    #[arg(long)]
+    half_life_seconds: Option<u64>,
+    /// Decay smoothly instead of halving in steps.
+    #[arg(long)]
+    continuous_decay: bool,
+    /// Annual rate of a target APR emission, in basis points.
+    #[arg(long)]
+    apr_bps: Option<u64>,
+    /// Cap on a target APR emission per second; zero for none.
+    #[arg(long, default_value_t = 0)]
+    max_per_second: u64,
+    /// Staked tokens making up 100% utilization.
+    #[arg(long)]
+    target_stake: Option<u64>,
+    /// Utilization where the APR curve kinks, in basis points.
+    #[arg(long, default_value_t = 10_000)]
+    kink_utilization_bps: u32,
+    /// Utilization APR of an empty pool, in basis points.
+    #[arg(long)]
+    max_apr_bps: Option<u32>,
+    /// Utilization APR at the kink, in basis points.
+    #[arg(long)]
+    kink_apr_bps: Option<u32>,
+    /// APR basis points lost per 100% of utilization past the kink.
+    #[arg(long, default_value_t = 0)]
+    slope_bps: u32,
+    /// Floor of the utilization APR, in basis points.
+    #[arg(long, default_value_t = 0)]
+    min_apr_bps: u32,
+}
+
+impl AlgorithmArgs {
+    fn reward_algorithm(&self, algorithm: Algorithm) -> Result<RewardAlgorithm> {
+        Ok(match algorithm {
+            Algorithm::PerTokenPerSecond => RewardAlgorithm::FixedPerTokenPerSecond,
+            Algorithm::TotalPerSecond => RewardAlgorithm::FixedTotalPerSecond,
+            Algorithm::Decaying => RewardAlgorithm::DecayingTotalPerSecond {
+                anchor_timestamp: self
+                    .decay_anchor_timestamp
+                    .ok_or_else(|| anyhow!("--decay-anchor-timestamp is required"))?,
+                half_life_seconds: self
+                    .half_life_seconds
+                    .ok_or_else(|| anyhow!("--half-life-seconds is required"))?,
+                continuous: self.continuous_decay,
+            },
+            Algorithm::TargetApr => RewardAlgorithm::TargetApr {
+                apr_bps: self
+                    .apr_bps
+                    .ok_or_else(|| anyhow!("--apr-bps is required"))?,
+                max_per_second: self.max_per_second,
+            },
+            Algorithm::UtilizationApr => RewardAlgorithm::UtilizationApr {
+                target_stake: self
+                    .target_stake
+                    .ok_or_else(|| anyhow!("--target-stake is required"))?,
+                kink_utilization_bps: self.kink_utilization_bps,
+                max_apr_bps: self
+                    .max_apr_bps
+                    .ok_or_else(|| anyhow!("--max-apr-bps is required"))?,
+                kink_apr_bps: self
+                    .kink_apr_bps
+                    .ok_or_else(|| anyhow!("--kink-apr-bps is required"))?,
+                slope_bps: self.slope_bps,
+                min_apr_bps: self.min_apr_bps,
+            },
+        })
+    }
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Create a staking pool owned by the signing authority.
+    CreatePool {
+        #[arg(long)]
+        mint: Pubkey,
+        #[arg(long)]
+        token_program: Option<Pubkey>,
+        #[arg(long, default_value_t = 0)]
+        index: u8,
+        #[arg(long)]
+        reward_rate: u64,
+        #[arg(long, value_enum)]
+        reward_algorithm: Algorithm,
+        #[command(flatten)]
+        algorithm_args: AlgorithmArgs,
+        #[arg(long)]
+        unbonding_seconds: u64,
+        /// Initial rewards transferred from the admin token account.
+        #[arg(long, default_value_t = 0)]
+        total_reward: u64,
+        /// Accept a mint with a permanent delegate.
+        #[arg(long)]
+        allow_permanent_delegate: bool,
+        /// Accept a Token-2022 mint with a freeze authority.
+        #[arg(long)]
+        allow_freeze_authority: bool,
+        /// Accept a mint with a transfer hook.
+        #[arg(long)]
+        allow_transfer_hook: bool,
+    },
+    /// Update pool parameters; omitted options are left unchanged.
+    Config {
+        #[arg(long)]
+        pool: Pubkey,
+        #[arg(long)]
+        min_stake_amount: Option<u64>,
+        #[arg(long)]
+        reward_rate: Option<u64>,
+        #[arg(long)]
+        unbonding_seconds: Option<u64>,
+        #[arg(long, value_enum)]
+        reward_algorithm: Option<Algorithm>,
+        #[command(flatten)]
+        algorithm_args: AlgorithmArgs,
+        /// Account allowed to slash the pool; the default pubkey disables slashing.
+        #[arg(long)]
+        slasher: Option<Pubkey>,
+        /// Seconds stake must stay in the pool before it can be unstaked.
+        #[arg(long)]
+        min_stake_duration: Option<u64>,
+        /// Reward multiplier after `loyalty_ramp_seconds`, in basis points (10000 = 1x).
+        #[arg(long)]
+        max_loyalty_multiplier: Option<u64>,
+        #[arg(long)]
+        loyalty_ramp_seconds: Option<u64>,
+        /// Release new unstake accounts linearly over the unbonding period.
+        #[arg(long)]
+        linear_unbonding: Option<bool>,
+        /// File with one allowed wallet per line; sets the allowlist root.
+        #[arg(long, conflicts_with = "clear_allowlist")]
+        allowlist: Option<PathBuf>,
+        /// Open staking to anyone again.
+        #[arg(long)]
+        clear_allowlist: bool,
+        /// Signer of the attestations required to stake; the default pubkey
+        /// stops requiring them.
+        #[arg(long)]
+        attestor: Option<Pubkey>,
+    },
+    /// Queue a reward rate and algorithm change taking effect at an exact time.
+    /// Not available for epoch mode pools.
+    ScheduleRewardChange {
+        #[arg(long)]
+        pool: Pubkey,
+        /// Unix timestamp the change applies from.
+        #[arg(long)]
+        effective_timestamp: u64,
+        #[arg(long)]
+        reward_rate: u64,
+        #[arg(long, value_enum)]
+        reward_algorithm: Algorithm,
+        #[command(flatten)]
+        algorithm_args: AlgorithmArgs,
+    },
+    /// Drop a queued reward change before it applies.
+    CancelRewardChange {
+        #[arg(long)]
+        pool: Pubkey,
+        #[arg(long)]
+        effective_timestamp: u64,
+    },
+    /// Pay the reward rate once per epoch, only to stake held through the
+    /// whole epoch. This cannot be undone.
+    EnableEpochMode {
+        #[arg(long)]
+        pool: Pubkey,
+        /// Epoch length in seconds.
+        #[arg(long, required_unless_present = "solana_epochs")]
+        epoch_seconds: Option<u64>,
+        /// Total reward paid per epoch, replacing the per second rate.
+        #[arg(long)]
+        reward_rate: u64,
+        /// Follow Solana epochs instead.
+        #[arg(long, conflicts_with = "epoch_seconds")]
+        solana_epochs: bool,
+    },
+    /// Fund the pool with additional rewards.
+    AddRewards {
+        #[command(flatten)]
+        pool: PoolArgs,
+        #[arg(long)]
+        amount: u64,
+    },
+    /// Record the rewards owed to a pool's stakers so `audit-pool` can
+    /// report on a pool created before they were tracked.
+    BackfillOwedRewards { pool: Pubkey },
+    /// Stake admin tokens into a wallet's position under a vesting schedule.
+    GrantVestingStake {
+        #[command(flatten)]
+        pool: PoolArgs,
+        #[arg(long)]
+        beneficiary: Pubkey,
+        #[arg(long)]
+        amount: u64,
+        /// Unix timestamp the linear unlock starts from.
+        #[arg(long)]
+        start_timestamp: u64,
+        /// Unix timestamp before which nothing unlocks.
+        #[arg(long)]
+        cliff_timestamp: u64,
+        /// Unix timestamp at which everything is unlocked.
+        #[arg(long)]
+        end_timestamp: u64,
+    },
+    /// Approve, as admin of either pool, moving stake from one pool to the other.
+    ApproveMigrationLink {
+        #[arg(long)]
+        from_pool: Pubkey,
+        #[arg(long)]
+        to_pool: Pubkey,
+        /// Withdraw a previous approval instead.
+        #[arg(long)]
+        revoke: bool,
+    },
+    /// Block a wallet from staking and claiming in the pool.
+    BlockUser {
+        #[arg(long)]
+        pool: Pubkey,
+        #[arg(long)]
+        user: Pubkey,
+        /// Issuer defined reason code recorded with the block.
+        #[arg(long, default_value_t = 0)]
+        reason: u16,
+        /// Also prevent the wallet from unstaking.
+        #[arg(long)]
+        freeze: bool,
+    },
+    /// Lift the block of a wallet, refunding its rent to the fee payer.
+    UnblockUser {
+        #[arg(long)]
+        pool: Pubkey,
+        #[arg(long)]
+        user: Pubkey,
+    },
+    /// Slash the pool as its slasher, sending the tokens to `recipient`.
+    Slash {
+        #[command(flatten)]
+        pool: PoolArgs,
+        #[arg(long)]
+        amount: u64,
+        /// Token account receiving the slashed tokens.
+        #[arg(long)]
+        recipient: Pubkey,
+        /// Also slash tokens still unbonding in unstake accounts.
+        #[arg(long)]
+        include_unbonding: bool,
+    },
+    /// Sign, as the pool attestor, an attestation letting `user` stake
+    /// until `expiry`; prints the signature.
+    SignAttestation {
+        #[arg(long)]
+        pool: Pubkey,
+        #[arg(long)]
+        user: Pubkey,
+        /// Unix timestamp the attestation expires at.
+        #[arg(long)]
+        expiry: u64,
+    },
+    /// Nominate a new admin, who must then run `accept-admin`.
+    TransferAdmin {
+        #[arg(long)]
+        pool: Pubkey,
+        #[arg(long)]
+        new_admin: Pubkey,
+    },
+    /// Accept a pending admin nomination.
+    AcceptAdmin {
+        #[arg(long)]
+        pool: Pubkey,
+    },
+    /// Stake tokens from the authority's token account.
+    Stake {
+        #[command(flatten)]
+        pool: PoolArgs,
+        #[arg(long)]
+        amount: u64,
+        /// Allowlist file of the pool, to prove the authority is on it.
+        #[arg(long)]
+        allowlist: Option<PathBuf>,
+        #[command(flatten)]
+        attestation: AttestationArgs,
+    },
+    /// Stake tokens from the authority's token account into another wallet's position.
+    StakeFor {
+        #[command(flatten)]
+        pool: PoolArgs,
+        #[arg(long)]
+        beneficiary: Pubkey,
+        #[arg(long)]
+        amount: u64,
+        /// Allowlist file of the pool, to prove the beneficiary is on it.
+        #[arg(long)]
+        allowlist: Option<PathBuf>,
+        #[command(flatten)]
+        attestation: AttestationArgs,
+    },
+    /// Start unbonding; prints the new unstake account.
+    Unstake {
+        #[arg(long)]
+        pool: Pubkey,
+        #[arg(long)]
+        amount: u64,
+        /// Stake account of the authority, looked up in the pool when omitted.
+        #[arg(long)]
+        stake_account: Option<Pubkey>,
+    },
+    /// Move stake units to another pool of the same mint without unbonding.
+    MigrateStake {
+        #[command(flatten)]
+        pool: PoolArgs,
+        #[arg(long)]
+        to_pool: Pubkey,
+        #[arg(long)]
+        amount: u64,
+        /// Stake account of the authority, looked up in the pool when omitted.
+        #[arg(long)]
+        stake_account: Option<Pubkey>,
+        /// Allowlist file of the destination pool.
+        #[arg(long)]
+        allowlist: Option<PathBuf>,
+        /// Attestation for the destination pool.
+        #[command(flatten)]
+        attestation: AttestationArgs,
+    },
+    /// Move the authority's legacy stake account to its pool scoped address,
+    /// merging it into any position there, so it can be topped up again.
+    RelocateStake {
+        #[arg(long)]
+        pool: Pubkey,
+    },
+    /// Withdraw the unlocked part of an unstake account.
+    Withdraw {
+        #[command(flatten)]
+        pool: PoolArgs,
+        #[arg(long)]
+        unstake_account: Pubkey,
+    },
+    /// Claim accrued rewards.
+    Claim {
+        #[command(flatten)]
+        pool: PoolArgs,
+        /// Add the rewards to the stake instead of paying them out.
+        #[arg(long)]
+        restake: bool,
+        /// Stake account of the authority, looked up in the pool when omitted.
+        #[arg(long)]
+        stake_account: Option<Pubkey>,
+    },
+    /// Pay out the ended epochs of an epoch mode pool.
+    SettleEpochs {
+        #[arg(long)]
+        pool: Pubkey,
+    },
+    /// Check the pool vault covers all tracked balances; the result is
+    /// emitted as an event in the transaction logs.
+    AuditPool {
+        #[command(flatten)]
+        pool: PoolArgs,
+    },
+    /// Print a staking pool as JSON.
+    ShowPool {
+        #[arg(long)]
+        pool: Pubkey,
+    },
+    /// Print a user's stake, pending reward and unstake accounts as JSON.
+    ShowPosition {
+        #[arg(long)]
+        pool: Pubkey,
+        /// Defaults to the `--keypair` pubkey.
+        #[arg(long)]
+        user: Option<Pubkey>,
+    },
+}
+
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+    let rpc = RpcClient::new_with_commitment(cli.url.clone(), CommitmentConfig::confirmed());
+    let authority = SignerArg::parse(&cli.keypair)?;
+    let fee_payer = match &cli.fee_payer {
+        Some(fee_payer) => SignerArg::parse(fee_payer)?,
+        None => authority.clone(),
+    };
+    let sender = TransactionSender {
+        rpc: &rpc,
+        fee_payer,
+        sign_only: cli.sign_only,
+        blockhash: cli.blockhash,
+    };
+
+    let authority_key = authority.pubkey();
+    let payer_key = sender.fee_payer.pubkey();
+
+    match cli.command {
+        Command::CreatePool {
+            mint,
+            token_program,
+            index,
+            reward_rate,
+            reward_algorithm,
+            algorithm_args,
+            unbonding_seconds,
+            total_reward,
+            allow_permanent_delegate,
+            allow_freeze_authority,
+            allow_transfer_hook,
+        } => {
+            let mut extension_policy = 0;
+            if allow_permanent_delegate {
+                extension_policy |= helper::ALLOW_PERMANENT_DELEGATE;
+            }
+            if allow_freeze_authority {
+                extension_policy |= helper::ALLOW_FREEZE_AUTHORITY;
+            }
+            if allow_transfer_hook {
+                extension_policy |= helper::ALLOW_TRANSFER_HOOK;
+            }
+
+            let token_program = match token_program {
+                Some(token_program) => token_program,
+                None => fetch_owner(&rpc, &mint)?,
+            };
+            let ix = instructions::initialize_staking_pool(
+                &authority_key,
+                &payer_key,
+                &mint,
+                &token_program,
+                InitializeStakingPoolParams {
+                    reward_rate,
+                    total_reward,
+                    unbonding_seconds,
+                    reward_algorithm: algorithm_args.reward_algorithm(reward_algorithm)?,
+                    index,
+                    extension_policy,
+                },
+            );
+            let (staking_pool, _) = pda::find_staking_pool_address(&mint, &authority_key, index);
+            println!("Staking pool: {}", staking_pool);
+            sender.process(vec![ix], &[&authority])
+        }
+        Command::Config {
+            pool,
+            min_stake_amount,
+            reward_rate,
+            unbonding_seconds,
+            reward_algorithm,
+            algorithm_args,
+            slasher,
+            min_stake_duration,
+            max_loyalty_multiplier,
+            loyalty_ramp_seconds,
+            linear_unbonding,
+            allowlist,
+            clear_allowlist,
+            attestor,
+        } => {
+            let allowlist_root = match allowlist {
+                Some(path) => Some(allowlist::root(&read_allowlist(&path)?)),
+                None => clear_allowlist.then_some([0u8; 32]),
+            };
+            let params = ConfigParams {
+                min_stake_amount,
+                reward_rate,
+                unbonding_seconds,
+                reward_algorithm: reward_algorithm
+                    .map(|algorithm| algorithm_args.reward_algorithm(algorithm))
+                    .transpose()?,
+                slasher,
+                min_stake_duration,
+                max_loyalty_multiplier,
+                loyalty_ramp_seconds,
+                linear_unbonding,
+                allowlist_root,
+                attestor,
+            };
+            if params == ConfigParams::default() {
+                return Err(anyhow!("nothing to configure"));
+            }
+            let ix = instructions::config(&authority_key, &pool, params);
+            sender.process(vec![ix], &[&authority])
+        }
+        Command::ScheduleRewardChange {
+            pool,
+            effective_timestamp,
+            reward_rate,
+            reward_algorithm,
+            algorithm_args,
+        } => {
+            let ix = instructions::schedule_reward_change(
+                &authority_key,
+                &payer_key,
+                &pool,
+                ScheduledRewardChange {
+                    effective_timestamp,
+                    reward_rate,
+                    reward_algorithm: algorithm_args.reward_algorithm(reward_algorithm)?,
+                },
+            );
+            sender.process(vec![ix], &[&authority])
+        }
+        Command::CancelRewardChange {
+            pool,
+            effective_timestamp,
+        } => {
+            let ix = instructions::cancel_reward_change(&authority_key, &pool, effective_timestamp);
+            sender.process(vec![ix], &[&authority])
+        }
+        Command::EnableEpochMode {
+            pool,
+            epoch_seconds,
+            solana_epochs: _,
+            reward_rate,
+        } => {
+            let epoch_length = epoch_seconds.unwrap_or(helper::SOLANA_EPOCHS);
+            let ix = instructions::enable_epoch_mode(
+                &authority_key,
+                &payer_key,
+                &pool,
+                epoch_length,
+                reward_rate,
+            );
+            sender.process(vec![ix], &[&authority])
+        }
+        Command::AddRewards { pool, amount } => {
+            let keys = resolve_pool_keys(&rpc, &pool)?;
+            let ix = instructions::add_rewards(&authority_key, &keys, amount);
+            sender.process(vec![ix], &[&authority])
+        }
+        Command::BackfillOwedRewards { pool } => {
+            let (owed_rewards, reward_per_share) = inspect::owed_rewards(&rpc, &pool)?;
+            let owed_rewards = u64::try_from(owed_rewards)
+                .map_err(|_| anyhow!("owed rewards {} overflow u64", owed_rewards))?;
+            let ix = instructions::backfill_owed_rewards(
+                &authority_key,
+                &pool,
+                owed_rewards,
+                reward_per_share,
+            );
+            sender.process(vec![ix], &[&authority])
+        }
+        Command::GrantVestingStake {
+            pool,
+            beneficiary,
+            amount,
+            start_timestamp,
+            cliff_timestamp,
+            end_timestamp,
+        } => {
+            let keys = resolve_pool_keys(&rpc, &pool)?;
+            let ix = instructions::grant_vesting_stake(
+                &authority_key,
+                &payer_key,
+                &keys,
+                &beneficiary,
+                GrantVestingStakeParams {
+                    amount,
+                    start_timestamp,
+                    cliff_timestamp,
+                    end_timestamp,
+                },
+            );
+            sender.process(vec![ix], &[&authority])
+        }
+        Command::ApproveMigrationLink {
+            from_pool,
+            to_pool,
+            revoke,
+        } => {
+            let ix = instructions::approve_migration_link(
+                &authority_key,
+                &payer_key,
+                &from_pool,
+                &to_pool,
+                !revoke,
+            );
+            sender.process(vec![ix], &[&authority])
+        }
+        Command::BlockUser {
+            pool,
+            user,
+            reason,
+            freeze,
+        } => {
+            let ix = instructions::set_blocklist(
+                &authority_key,
+                &payer_key,
+                &pool,
+                &user,
+                reason,
+                freeze,
+            );
+            sender.process(vec![ix], &[&authority])
+        }
+        Command::UnblockUser { pool, user } => {
+            let ix = instructions::clear_blocklist(&authority_key, &payer_key, &pool, &user);
+            sender.process(vec![ix], &[&authority])
+        }
+        Command::Slash {
+            pool,
+            amount,
+            recipient,
+            include_unbonding,
+        } => {
+            let keys = resolve_pool_keys(&rpc, &pool)?;
+            let ix =
+                instructions::slash(&authority_key, &keys, &recipient, amount, include_unbonding);
+            sender.process(vec![ix], &[&authority])
+        }
+        Command::SignAttestation { pool, user, expiry } => {
+            let message = attestation::message(&pool, &user, expiry);
+            println!("{}", authority.sign_message(&message)?);
+            Ok(())
+        }
+        Command::TransferAdmin { pool, new_admin } => {
+            let ix = instructions::transfer_admin(&authority_key, &pool, new_admin);
+            sender.process(vec![ix], &[&authority])
+        }
+        Command::AcceptAdmin { pool } => {
+            let ix = instructions::accept_admin(&authority_key, &pool);
+            sender.process(vec![ix], &[&authority])
+        }
+        Command::Stake {
+            pool,
+            amount,
+            allowlist,
+            attestation,
+        } => {
+            let keys = resolve_pool_keys(&rpc, &pool)?;
+            let proof = allowlist_proof(allowlist.as_deref(), &authority_key)?;
+            let mut ixs = attestation_instructions(&rpc, &attestation, &pool.pool, &authority_key)?;
+            ixs.push(instructions::stake(
+                &authority_key,
+                &payer_key,
+                &keys,
+                amount,
+                proof,
+            ));
+            sender.process(ixs, &[&authority])
+        }
+        Command::StakeFor {
+            pool,
+            beneficiary,
+            amount,
+            allowlist,
+            attestation,
+        } => {
+            let keys = resolve_pool_keys(&rpc, &pool)?;
+            let proof = allowlist_proof(allowlist.as_deref(), &beneficiary)?;
+            let mut ixs = attestation_instructions(&rpc, &attestation, &pool.pool, &beneficiary)?;
+            ixs.push(instructions::stake_for(
+                &authority_key,
+                &payer_key,
+                &keys,
+                &beneficiary,
+                amount,
+                proof,
+            ));
+            sender.process(ixs, &[&authority])
+        }
+        Command::Unstake {
+            pool,
+            amount,
+            stake_account,
+        } => {
+            let unstake_account = SignerArg::from(Keypair::new());
+            let stake_account = resolve_stake_account(&rpc, stake_account, &pool, &authority_key)?;
+            let ix = instructions::unstake(
+                &authority_key,
+                &payer_key,
+                &pool,
+                &stake_account,
+                &unstake_account.pubkey(),
+                amount,
+            );
+            println!("Unstake account: {}", unstake_account.pubkey());
+            sender.process(vec![ix], &[&authority, &unstake_account])
+        }
+        Command::MigrateStake {
+            pool,
+            to_pool,
+            amount,
+            stake_account,
+            allowlist,
+            attestation,
+        } => {
+            let keys = resolve_pool_keys(&rpc, &pool)?;
+            let stake_account =
+                resolve_stake_account(&rpc, stake_account, &pool.pool, &authority_key)?;
+            let proof = allowlist_proof(allowlist.as_deref(), &authority_key)?;
+            let mut ixs = attestation_instructions(&rpc, &attestation, &to_pool, &authority_key)?;
+            ixs.push(instructions::migrate_stake(
+                &authority_key,
+                &payer_key,
+                &keys,
+                &to_pool,
+                &stake_account,
+                amount,
+                proof,
+            ));
+            sender.process(ixs, &[&authority])
+        }
+        Command::RelocateStake { pool } => {
+            let ix = instructions::relocate_stake(&authority_key, &payer_key, &pool);
+            sender.process(vec![ix], &[&authority])
+        }
+        Command::Withdraw {
+            pool,
+            unstake_account,
+        } => {
+            let keys = resolve_pool_keys(&rpc, &pool)?;
+            let ix = instructions::withdraw(&authority_key, &payer_key, &keys, &unstake_account);
+            sender.process(vec![ix], &[&authority])
+        }
+        Command::Claim {
+            pool,
+            restake,
+            stake_account,
+        } => {
+            let keys = resolve_pool_keys(&rpc, &pool)?;
+            let stake_account =
+                resolve_stake_account(&rpc, stake_account, &pool.pool, &authority_key)?;
+            let ix =
+                instructions::claim(&authority_key, &payer_key, &keys, &stake_account, restake);
+            sender.process(vec![ix], &[&authority])
+        }
+        Command::SettleEpochs { pool } => {
+            let ix = instructions::settle_epochs(&pool);
+            sender.process(vec![ix], &[])
+        }
+        Command::AuditPool { pool } => {
+            let keys = resolve_pool_keys(&rpc, &pool)?;
+            let ix = instructions::audit_pool(&keys);
+            sender.process(vec![ix], &[])
+        }
+        Command::ShowPool { pool } => inspect::show_pool(&rpc, &pool),
+        Command::ShowPosition { pool, user } => {
+            inspect::show_position(&rpc, &pool, &user.unwrap_or(authority_key))
+        }
+    }
+}
+
+fn fetch_owner(rpc: &RpcClient, address: &Pubkey) -> Result<Pubkey> {
+    Ok(rpc
+        .get_account(address)
+        .with_context(|| format!("fetching account {}", address))?
+        .owner)
+}
+
+fn read_allowlist(path: &Path) -> Result<Vec<Pubkey>> {
+    std::fs::read_to_string(path)
+        .with_context(|| format!("reading allowlist {}", path.display()))?
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| Pubkey::from_str(line).with_context(|| format!("invalid pubkey {}", line)))
+        .collect()
+}
+
+fn allowlist_proof(path: Option<&Path>, user: &Pubkey) -> Result<Vec<[u8; 32]>> {
+    let Some(path) = path else {
+        return Ok(vec![]);
+    };
+
+    allowlist::proof(&read_allowlist(path)?, user)
+        .ok_or_else(|| anyhow!("{} is not on allowlist {}", user, path.display()))
+}
+
+/// Ed25519 verification to put right before an instruction staking `user`
+/// into `pool`, if an attestation was given.
+fn attestation_instructions(
+    rpc: &RpcClient,
+    args: &AttestationArgs,
+    pool: &Pubkey,
+    user: &Pubkey,
+) -> Result<Vec<Instruction>> {
+    let (Some(signature), Some(expiry)) = (args.attestation_signature, args.attestation_expiry)
+    else {
+        return Ok(vec![]);
+    };
+    let attestor = match args.attestor {
+        Some(attestor) => attestor,
+        None => {
+            let data = rpc
+                .get_account_data(pool)
+                .with_context(|| format!("fetching staking pool {}", pool))?;
+            accounts::deserialize_staking_pool(&data)?.attestor
+        }
+    };
+
+    let message = attestation::message(pool, user, expiry);
+    let signature: [u8; 64] = signature.into();
+    Ok(vec![attestation::verify_instruction(
+        &attestor, &signature, &message,
+    )])
+}
+
+fn resolve_stake_account(
+    rpc: &RpcClient,
+    stake_account: Option<Pubkey>,
+    pool: &Pubkey,
+    user: &Pubkey,
+) -> Result<Pubkey> {
+    if let Some(stake_account) = stake_account {
+        return Ok(stake_account);
+    }
+
+    match inspect::find_stake_account(rpc, pool, user)? {
+        Some((address, _)) => Ok(address),
+        None => Err(anyhow!("no stake account for {} in pool {}", user, pool)),
+    }
+}
+
+fn resolve_pool_keys(rpc: &RpcClient, args: &PoolArgs) -> Result<PoolKeys> {
+    let token_mint = match args.mint {
+        Some(mint) => mint,
+        None => {
+            let data = rpc
+                .get_account_data(&args.pool)
+                .with_context(|| format!("fetching staking pool {}", args.pool))?;
+            accounts::deserialize_staking_pool(&data)?.token_mint
+        }
+    };
+    let token_program = match args.token_program {
+        Some(token_program) => token_program,
+        None => fetch_owner(rpc, &token_mint)?,
+    };
+
+    Ok(PoolKeys::new(args.pool, token_mint, token_program))
+}
@@ -0,0 +1,140 @@
+use anyhow::{anyhow, Context, Result};
+use base64::Engine;
+use serde_json::json;
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::hash::Hash;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{read_keypair_file, Keypair, Signature, Signer};
+use solana_sdk::transaction::Transaction;
+use std::rc::Rc;
+use std::str::FromStr;
+
+/// A signer given on the command line: either a keypair file we can sign
+/// with, or a bare pubkey whose signature is collected offline.
+#[derive(Clone)]
+pub enum SignerArg {
+    Keypair(Rc<Keypair>),
+    Pubkey(Pubkey),
+}
+
+impl SignerArg {
+    pub fn parse(value: &str) -> Result<Self> {
+        if let Ok(pubkey) = Pubkey::from_str(value) {
+            return Ok(SignerArg::Pubkey(pubkey));
+        }
+
+        let path = match value.strip_prefix("~/") {
+            Some(rest) => format!("{}/{}", std::env::var("HOME")?, rest),
+            None => value.to_string(),
+        };
+        let keypair =
+            read_keypair_file(&path).map_err(|e| anyhow!("reading keypair {}: {}", path, e))?;
+
+        Ok(SignerArg::Keypair(Rc::new(keypair)))
+    }
+
+    pub fn pubkey(&self) -> Pubkey {
+        match self {
+            SignerArg::Keypair(keypair) => keypair.pubkey(),
+            SignerArg::Pubkey(pubkey) => *pubkey,
+        }
+    }
+
+    /// Signs an off-chain `message`, which needs a keypair file.
+    pub fn sign_message(&self, message: &[u8]) -> Result<Signature> {
+        let keypair = self
+            .keypair()
+            .ok_or_else(|| anyhow!("signing a message needs a keypair file"))?;
+
+        Ok(keypair.sign_message(message))
+    }
+
+    fn keypair(&self) -> Option<&Keypair> {
+        match self {
+            SignerArg::Keypair(keypair) => Some(keypair),
+            SignerArg::Pubkey(_) => None,
+        }
+    }
+}
+
+impl From<Keypair> for SignerArg {
+    fn from(keypair: Keypair) -> Self {
+        SignerArg::Keypair(Rc::new(keypair))
+    }
+}
+
+pub struct TransactionSender<'a> {
+    pub rpc: &'a RpcClient,
+    pub fee_payer: SignerArg,
+    pub sign_only: bool,
+    pub blockhash: Option<Hash>,
+}
+
+impl<'a> TransactionSender<'a> {
+    /// Signs `instructions` with every available keypair, then either sends
+    /// the transaction or, in sign-only mode, prints it for the remaining
+    /// signers.
+    pub fn process(&self, instructions: Vec<Instruction>, signers: &[&SignerArg]) -> Result<()> {
+        let blockhash = match self.blockhash {
+            Some(blockhash) => blockhash,
+            None => self
+                .rpc
+                .get_latest_blockhash()
+                .context("fetching latest blockhash")?,
+        };
+
+        let mut tx = Transaction::new_with_payer(&instructions, Some(&self.fee_payer.pubkey()));
+
+        let mut keypairs: Vec<&dyn Signer> = vec![];
+        for signer in std::iter::once(&self.fee_payer).chain(signers.iter().copied()) {
+            if let Some(keypair) = signer.keypair() {
+                if !keypairs.iter().any(|k| k.pubkey() == keypair.pubkey()) {
+                    keypairs.push(keypair);
+                }
+            }
+        }
+        tx.try_partial_sign(&keypairs, blockhash)?;
+
+        let required =
+            &tx.message.account_keys[..tx.message.header.num_required_signatures as usize];
+        let missing: Vec<String> = required
+            .iter()
+            .zip(tx.signatures.iter())
+            .filter(|(_, signature)| **signature == Default::default())
+            .map(|(pubkey, _)| pubkey.to_string())
+            .collect();
+
+        if self.sign_only {
+            let serialized = bincode::serialize(&tx)?;
+            let output = json!({
+                "blockhash": blockhash.to_string(),
+                "transaction": base64::engine::general_purpose::STANDARD.encode(serialized),
+                "signers": required
+                    .iter()
+                    .zip(tx.signatures.iter())
+                    .filter(|(_, signature)| **signature != Default::default())
+                    .map(|(pubkey, signature)| format!("{}={}", pubkey, signature))
+                    .collect::<Vec<_>>(),
+                "missing_signers": missing,
+            });
+            println!("{}", serde_json::to_string_pretty(&output)?);
+            return Ok(());
+        }
+
+        if !missing.is_empty() {
+            return Err(anyhow!(
+                "missing signatures for {}, rerun with --sign-only",
+                missing.join(", ")
+            ));
+        }
+
+        let signature = self
+            .rpc
+            .send_and_confirm_transaction(&tx)
+            .context("sending transaction")?;
+        println!("Signature: {}", signature);
+
+        Ok(())
+    }
+}
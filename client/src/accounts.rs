@@ -0,0 +1,34 @@
+use anchor_lang::{AccountDeserialize, Result};
+use staking_program::{
+    BalanceCheckpoints, Blocklist, MigrationLink, StakeAccount, StakingPool, UnstakeAccount,
+    VoterWeightRecord,
+};
+
+/// Decodes raw account data, including the 8-byte anchor discriminator.
+pub fn deserialize_staking_pool(mut data: &[u8]) -> Result<StakingPool> {
+    StakingPool::try_deserialize(&mut data)
+}
+
+pub fn deserialize_stake_account(mut data: &[u8]) -> Result<StakeAccount> {
+    StakeAccount::try_deserialize(&mut data)
+}
+
+pub fn deserialize_unstake_account(mut data: &[u8]) -> Result<UnstakeAccount> {
+    UnstakeAccount::try_deserialize(&mut data)
+}
+
+pub fn deserialize_voter_weight_record(mut data: &[u8]) -> Result<VoterWeightRecord> {
+    VoterWeightRecord::try_deserialize(&mut data)
+}
+
+pub fn deserialize_balance_checkpoints(mut data: &[u8]) -> Result<BalanceCheckpoints> {
+    BalanceCheckpoints::try_deserialize(&mut data)
+}
+
+pub fn deserialize_migration_link(mut data: &[u8]) -> Result<MigrationLink> {
+    MigrationLink::try_deserialize(&mut data)
+}
+
+pub fn deserialize_blocklist(mut data: &[u8]) -> Result<Blocklist> {
+    Blocklist::try_deserialize(&mut data)
+}
@@ -0,0 +1,97 @@
+//! Merkle trees for pool allowlists, matching `StakingPool::check_allowlist`.
+
+use anchor_lang::prelude::Pubkey;
+use staking_program::helper;
+
+fn parent_level(level: &[[u8; 32]]) -> Vec<[u8; 32]> {
+    level
+        .chunks(2)
+        .map(|pair| match pair {
+            [left, right] => helper::allowlist_node(left, right),
+            [single] => *single,
+            _ => unreachable!(),
+        })
+        .collect()
+}
+
+/// Root of the allowlist of `users`, in the given order.
+pub fn root(users: &[Pubkey]) -> [u8; 32] {
+    let mut level: Vec<[u8; 32]> = users.iter().map(helper::allowlist_leaf).collect();
+    if level.is_empty() {
+        return [0u8; 32];
+    }
+    while level.len() > 1 {
+        level = parent_level(&level);
+    }
+
+    level[0]
+}
+
+/// Proof that `user` is in the allowlist of `users`, or `None` if it isn't.
+pub fn proof(users: &[Pubkey], user: &Pubkey) -> Option<Vec<[u8; 32]>> {
+    let mut index = users.iter().position(|candidate| candidate == user)?;
+    let mut level: Vec<[u8; 32]> = users.iter().map(helper::allowlist_leaf).collect();
+    let mut proof = vec![];
+    while level.len() > 1 {
+        // An unpaired last node is promoted as is, with no sibling to prove.
+        if let Some(sibling) = level.get(index ^ 1) {
+            proof.push(*sibling);
+        }
+        level = parent_level(&level);
+        index /= 2;
+    }
+
+    Some(proof)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_lang::{error, AccountDeserialize};
+    use staking_program::{Errors, StakingPool};
+
+    fn pool_with_root(allowlist_root: [u8; 32]) -> StakingPool {
+        let data = vec![0u8; StakingPool::SPACE];
+        let mut staking_pool = StakingPool::try_deserialize_unchecked(&mut &data[..]).unwrap();
+        staking_pool.allowlist_root = allowlist_root;
+        staking_pool
+    }
+
+    #[test]
+    fn proofs_verify_against_pool_root() {
+        // Odd sizes leave an unpaired node to promote at some level.
+        for size in [1, 2, 3, 5, 8, 11] {
+            let users: Vec<Pubkey> = (0..size).map(|_| Pubkey::new_unique()).collect();
+            let staking_pool = pool_with_root(root(&users));
+
+            for user in &users {
+                let proof = proof(&users, user).unwrap();
+                staking_pool.check_allowlist(user, &proof).unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn outsiders_are_rejected() {
+        let users: Vec<Pubkey> = (0..5).map(|_| Pubkey::new_unique()).collect();
+        let outsider = Pubkey::new_unique();
+        let staking_pool = pool_with_root(root(&users));
+
+        assert!(proof(&users, &outsider).is_none());
+        let borrowed = proof(&users, &users[0]).unwrap();
+        assert_eq!(
+            staking_pool
+                .check_allowlist(&outsider, &borrowed)
+                .unwrap_err(),
+            error!(Errors::NotAllowlisted)
+        );
+    }
+
+    #[test]
+    fn empty_allowlist_admits_everyone() {
+        assert_eq!(root(&[]), [0u8; 32]);
+        pool_with_root(root(&[]))
+            .check_allowlist(&Pubkey::new_unique(), &[])
+            .unwrap();
+    }
+}
@@ -0,0 +1,104 @@
+//! Ed25519 attestations for pools with an attestor, matching
+//! `helper::check_attestation`.
+
+use anchor_lang::prelude::Pubkey;
+use anchor_lang::solana_program::ed25519_program;
+use anchor_lang::solana_program::instruction::Instruction;
+use staking_program::helper;
+
+const DATA_START: usize = 2 + 14;
+const PUBLIC_KEY_OFFSET: usize = DATA_START;
+const SIGNATURE_OFFSET: usize = PUBLIC_KEY_OFFSET + 32;
+const MESSAGE_OFFSET: usize = SIGNATURE_OFFSET + 64;
+
+/// Message the attestor signs to let `user` stake in `staking_pool` until
+/// `expiry`.
+pub fn message(staking_pool: &Pubkey, user: &Pubkey, expiry: u64) -> Vec<u8> {
+    helper::attestation_message(staking_pool, user, expiry).to_vec()
+}
+
+/// Ed25519 program instruction verifying `signature` of `message` by
+/// `attestor`. It must come right before the instruction it attests for.
+pub fn verify_instruction(attestor: &Pubkey, signature: &[u8; 64], message: &[u8]) -> Instruction {
+    let mut data = Vec::with_capacity(MESSAGE_OFFSET + message.len());
+    data.extend_from_slice(&[1, 0]);
+    for offset in [
+        SIGNATURE_OFFSET,
+        u16::MAX as usize,
+        PUBLIC_KEY_OFFSET,
+        u16::MAX as usize,
+        MESSAGE_OFFSET,
+        message.len(),
+        u16::MAX as usize,
+    ] {
+        data.extend_from_slice(&(offset as u16).to_le_bytes());
+    }
+    data.extend_from_slice(attestor.as_ref());
+    data.extend_from_slice(signature);
+    data.extend_from_slice(message);
+
+    Instruction {
+        program_id: ed25519_program::ID,
+        accounts: vec![],
+        data,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_lang::error;
+    use staking_program::Errors;
+
+    #[test]
+    fn verify_instruction_layout_matches_program() {
+        let (attestor, staking_pool, user) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let instruction = verify_instruction(
+            &attestor,
+            &[7u8; 64],
+            &message(&staking_pool, &user, 1_700_000_000),
+        );
+
+        assert_eq!(instruction.program_id, ed25519_program::ID);
+        assert!(instruction.accounts.is_empty());
+        assert_eq!(
+            instruction.data[SIGNATURE_OFFSET..MESSAGE_OFFSET],
+            [7u8; 64]
+        );
+        assert_eq!(
+            helper::attestation_expiry(&instruction.data, &attestor, &staking_pool, &user).unwrap(),
+            1_700_000_000
+        );
+    }
+
+    #[test]
+    fn attestation_is_bound_to_attestor_pool_and_user() {
+        let (attestor, staking_pool, user) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let other = Pubkey::new_unique();
+        let instruction = verify_instruction(
+            &attestor,
+            &[0u8; 64],
+            &message(&staking_pool, &user, 1_700_000_000),
+        );
+
+        for (attestor, staking_pool, user) in [
+            (&other, &staking_pool, &user),
+            (&attestor, &other, &user),
+            (&attestor, &staking_pool, &other),
+        ] {
+            assert_eq!(
+                helper::attestation_expiry(&instruction.data, attestor, staking_pool, user)
+                    .unwrap_err(),
+                error!(Errors::InvalidAttestation)
+            );
+        }
+    }
+}
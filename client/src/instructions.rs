@@ -0,0 +1,591 @@
+//! Instruction builders for every staking program entrypoint.
+//!
+//! For Token-2022 mints with a transfer hook, append the hook's extra account
+//! metas to the returned instruction's `accounts` before sending it.
+
+use crate::pda;
+use anchor_lang::prelude::Pubkey;
+use anchor_lang::solana_program::instruction::{AccountMeta, Instruction};
+use anchor_lang::solana_program::sysvar;
+use anchor_lang::{system_program, InstructionData, ToAccountMetas};
+use anchor_spl::associated_token;
+use staking_program::{
+    accounts, instruction, ConfigParams, GrantVestingStakeParams, InitializeStakingPoolParams,
+    ScheduledRewardChange,
+};
+
+/// Accounts shared by every instruction that moves tokens in or out of a pool.
+#[derive(Clone, Copy, Debug)]
+pub struct PoolKeys {
+    pub staking_pool: Pubkey,
+    pub token_mint: Pubkey,
+    pub token_program: Pubkey,
+}
+
+impl PoolKeys {
+    pub fn new(staking_pool: Pubkey, token_mint: Pubkey, token_program: Pubkey) -> Self {
+        Self {
+            staking_pool,
+            token_mint,
+            token_program,
+        }
+    }
+
+    pub fn token_account(&self, owner: &Pubkey) -> Pubkey {
+        pda::token_account_address(owner, &self.token_mint, &self.token_program)
+    }
+
+    pub fn pool_token_account(&self) -> Pubkey {
+        self.token_account(&self.staking_pool)
+    }
+}
+
+fn build(accounts: impl ToAccountMetas, data: impl InstructionData) -> Instruction {
+    Instruction {
+        program_id: staking_program::ID,
+        accounts: accounts.to_account_metas(None),
+        data: data.data(),
+    }
+}
+
+pub fn initialize_staking_pool(
+    admin: &Pubkey,
+    rent_payer: &Pubkey,
+    token_mint: &Pubkey,
+    token_program: &Pubkey,
+    params: InitializeStakingPoolParams,
+) -> Instruction {
+    let (staking_pool, _) = pda::find_staking_pool_address(token_mint, admin, params.index);
+    let keys = PoolKeys::new(staking_pool, *token_mint, *token_program);
+
+    build(
+        accounts::InitializeStakingPool {
+            admin: *admin,
+            rent_payer: *rent_payer,
+            token_mint: *token_mint,
+            staking_pool,
+            admin_token_account: keys.token_account(admin),
+            pool_token_account: keys.pool_token_account(),
+            token_program: *token_program,
+            associated_token_program: associated_token::ID,
+            system_program: system_program::ID,
+        },
+        instruction::InitializeStakingPool { params },
+    )
+}
+
+pub fn transfer_admin(admin: &Pubkey, staking_pool: &Pubkey, new_admin: Pubkey) -> Instruction {
+    build(
+        accounts::TransferAdmin {
+            admin: *admin,
+            staking_pool: *staking_pool,
+        },
+        instruction::TransferAdmin { new_admin },
+    )
+}
+
+pub fn accept_admin(pending_admin: &Pubkey, staking_pool: &Pubkey) -> Instruction {
+    build(
+        accounts::AcceptAdmin {
+            pending_admin: *pending_admin,
+            staking_pool: *staking_pool,
+        },
+        instruction::AcceptAdmin {},
+    )
+}
+
+pub fn config(admin: &Pubkey, staking_pool: &Pubkey, params: ConfigParams) -> Instruction {
+    build(
+        accounts::Config {
+            admin: *admin,
+            staking_pool: *staking_pool,
+        },
+        instruction::Config { params },
+    )
+}
+
+pub fn schedule_reward_change(
+    admin: &Pubkey,
+    rent_payer: &Pubkey,
+    staking_pool: &Pubkey,
+    change: ScheduledRewardChange,
+) -> Instruction {
+    build(
+        accounts::ScheduleRewardChange {
+            admin: *admin,
+            rent_payer: *rent_payer,
+            staking_pool: *staking_pool,
+            system_program: system_program::ID,
+        },
+        instruction::ScheduleRewardChange { change },
+    )
+}
+
+pub fn cancel_reward_change(
+    admin: &Pubkey,
+    staking_pool: &Pubkey,
+    effective_timestamp: u64,
+) -> Instruction {
+    build(
+        accounts::CancelRewardChange {
+            admin: *admin,
+            staking_pool: *staking_pool,
+        },
+        instruction::CancelRewardChange {
+            effective_timestamp,
+        },
+    )
+}
+
+/// `epoch_length` is in seconds, or `staking_program::helper::SOLANA_EPOCHS`.
+/// `reward_rate` is the total reward of each epoch.
+pub fn enable_epoch_mode(
+    admin: &Pubkey,
+    rent_payer: &Pubkey,
+    staking_pool: &Pubkey,
+    epoch_length: u64,
+    reward_rate: u64,
+) -> Instruction {
+    build(
+        accounts::EnableEpochMode {
+            admin: *admin,
+            rent_payer: *rent_payer,
+            staking_pool: *staking_pool,
+            system_program: system_program::ID,
+        },
+        instruction::EnableEpochMode {
+            epoch_length,
+            reward_rate,
+        },
+    )
+}
+
+pub fn approve_migration_link(
+    admin: &Pubkey,
+    rent_payer: &Pubkey,
+    from_pool: &Pubkey,
+    to_pool: &Pubkey,
+    approved: bool,
+) -> Instruction {
+    build(
+        accounts::ApproveMigrationLink {
+            admin: *admin,
+            rent_payer: *rent_payer,
+            from_pool: *from_pool,
+            to_pool: *to_pool,
+            migration_link: pda::find_migration_link_address(from_pool, to_pool).0,
+            system_program: system_program::ID,
+        },
+        instruction::ApproveMigrationLink { approved },
+    )
+}
+
+pub fn set_blocklist(
+    admin: &Pubkey,
+    rent_payer: &Pubkey,
+    staking_pool: &Pubkey,
+    user: &Pubkey,
+    reason: u16,
+    frozen: bool,
+) -> Instruction {
+    build(
+        accounts::SetBlocklist {
+            admin: *admin,
+            rent_payer: *rent_payer,
+            staking_pool: *staking_pool,
+            blocklist: pda::find_blocklist_address(staking_pool, user).0,
+            system_program: system_program::ID,
+        },
+        instruction::SetBlocklist {
+            user: *user,
+            reason,
+            frozen,
+        },
+    )
+}
+
+pub fn clear_blocklist(
+    admin: &Pubkey,
+    rent_receiver: &Pubkey,
+    staking_pool: &Pubkey,
+    user: &Pubkey,
+) -> Instruction {
+    build(
+        accounts::ClearBlocklist {
+            admin: *admin,
+            rent_receiver: *rent_receiver,
+            staking_pool: *staking_pool,
+            blocklist: pda::find_blocklist_address(staking_pool, user).0,
+        },
+        instruction::ClearBlocklist {},
+    )
+}
+
+/// `owed_rewards` as computed by `rewards::owed_rewards` against a pool
+/// with `reward_per_share`.
+pub fn backfill_owed_rewards(
+    admin: &Pubkey,
+    staking_pool: &Pubkey,
+    owed_rewards: u64,
+    reward_per_share: u128,
+) -> Instruction {
+    build(
+        accounts::BackfillOwedRewards {
+            admin: *admin,
+            staking_pool: *staking_pool,
+        },
+        instruction::BackfillOwedRewards {
+            owed_rewards,
+            reward_per_share,
+        },
+    )
+}
+
+pub fn add_rewards(admin: &Pubkey, keys: &PoolKeys, amount: u64) -> Instruction {
+    build(
+        accounts::AddRewards {
+            admin: *admin,
+            staking_pool: keys.staking_pool,
+            admin_token_account: keys.token_account(admin),
+            pool_token_account: keys.pool_token_account(),
+            token_mint: keys.token_mint,
+            token_program: keys.token_program,
+            associated_token_program: associated_token::ID,
+        },
+        instruction::AddRewards { amount },
+    )
+}
+
+pub fn grant_vesting_stake(
+    admin: &Pubkey,
+    rent_payer: &Pubkey,
+    keys: &PoolKeys,
+    beneficiary: &Pubkey,
+    params: GrantVestingStakeParams,
+) -> Instruction {
+    let (stake_account, _) = pda::find_stake_account_address(&keys.staking_pool, beneficiary);
+
+    build(
+        accounts::GrantVestingStake {
+            admin: *admin,
+            rent_payer: *rent_payer,
+            staking_pool: keys.staking_pool,
+            token_mint: keys.token_mint,
+            admin_token_account: keys.token_account(admin),
+            pool_token_account: keys.pool_token_account(),
+            stake_account,
+            pool_checkpoints: pda::find_checkpoints_address(&keys.staking_pool).0,
+            stake_checkpoints: pda::find_checkpoints_address(&stake_account).0,
+            token_program: keys.token_program,
+            associated_token_program: associated_token::ID,
+            system_program: system_program::ID,
+        },
+        instruction::GrantVestingStake {
+            beneficiary: *beneficiary,
+            params,
+        },
+    )
+}
+
+/// `recipient` is a token account of the pool mint.
+pub fn slash(
+    slasher: &Pubkey,
+    keys: &PoolKeys,
+    recipient: &Pubkey,
+    amount: u64,
+    include_unbonding: bool,
+) -> Instruction {
+    build(
+        accounts::Slash {
+            slasher: *slasher,
+            staking_pool: keys.staking_pool,
+            token_mint: keys.token_mint,
+            pool_token_account: keys.pool_token_account(),
+            recipient: *recipient,
+            token_program: keys.token_program,
+            associated_token_program: associated_token::ID,
+        },
+        instruction::Slash {
+            amount,
+            include_unbonding,
+        },
+    )
+}
+
+pub fn stake(
+    user: &Pubkey,
+    rent_payer: &Pubkey,
+    keys: &PoolKeys,
+    stake_amount: u64,
+    proof: Vec<[u8; 32]>,
+) -> Instruction {
+    let (stake_account, _) = pda::find_stake_account_address(&keys.staking_pool, user);
+
+    build(
+        accounts::Stake {
+            user: *user,
+            rent_payer: *rent_payer,
+            staking_pool: keys.staking_pool,
+            token_mint: keys.token_mint,
+            user_token_account: keys.token_account(user),
+            pool_token_account: keys.pool_token_account(),
+            stake_account,
+            pool_checkpoints: pda::find_checkpoints_address(&keys.staking_pool).0,
+            stake_checkpoints: pda::find_checkpoints_address(&stake_account).0,
+            instructions_sysvar: sysvar::instructions::ID,
+            blocklist: pda::find_blocklist_address(&keys.staking_pool, user).0,
+            token_program: keys.token_program,
+            associated_token_program: associated_token::ID,
+            system_program: system_program::ID,
+        },
+        instruction::Stake {
+            stake_amount,
+            proof,
+        },
+    )
+}
+
+pub fn stake_for(
+    funder: &Pubkey,
+    rent_payer: &Pubkey,
+    keys: &PoolKeys,
+    beneficiary: &Pubkey,
+    stake_amount: u64,
+    proof: Vec<[u8; 32]>,
+) -> Instruction {
+    let (stake_account, _) = pda::find_stake_account_address(&keys.staking_pool, beneficiary);
+
+    build(
+        accounts::StakeFor {
+            funder: *funder,
+            rent_payer: *rent_payer,
+            staking_pool: keys.staking_pool,
+            token_mint: keys.token_mint,
+            funder_token_account: keys.token_account(funder),
+            pool_token_account: keys.pool_token_account(),
+            stake_account,
+            pool_checkpoints: pda::find_checkpoints_address(&keys.staking_pool).0,
+            stake_checkpoints: pda::find_checkpoints_address(&stake_account).0,
+            instructions_sysvar: sysvar::instructions::ID,
+            blocklist: pda::find_blocklist_address(&keys.staking_pool, beneficiary).0,
+            funder_blocklist: pda::find_blocklist_address(&keys.staking_pool, funder).0,
+            token_program: keys.token_program,
+            associated_token_program: associated_token::ID,
+            system_program: system_program::ID,
+        },
+        instruction::StakeFor {
+            beneficiary: *beneficiary,
+            stake_amount,
+            proof,
+        },
+    )
+}
+
+/// `unstake_account` is a fresh keypair that must also sign the transaction.
+pub fn unstake(
+    user: &Pubkey,
+    rent_payer: &Pubkey,
+    staking_pool: &Pubkey,
+    stake_account: &Pubkey,
+    unstake_account: &Pubkey,
+    unstake_amount: u64,
+) -> Instruction {
+    build(
+        accounts::Unstake {
+            user: *user,
+            rent_payer: *rent_payer,
+            staking_pool: *staking_pool,
+            stake_account: *stake_account,
+            unstake_account: *unstake_account,
+            pool_checkpoints: pda::find_checkpoints_address(staking_pool).0,
+            stake_checkpoints: pda::find_checkpoints_address(stake_account).0,
+            blocklist: pda::find_blocklist_address(staking_pool, user).0,
+            system_program: system_program::ID,
+        },
+        instruction::Unstake { unstake_amount },
+    )
+}
+
+pub fn withdraw(
+    user: &Pubkey,
+    rent_payer: &Pubkey,
+    keys: &PoolKeys,
+    unstake_account: &Pubkey,
+) -> Instruction {
+    build(
+        accounts::Withdraw {
+            user: *user,
+            rent_payer: *rent_payer,
+            staking_pool: keys.staking_pool,
+            unstake_account: *unstake_account,
+            token_mint: keys.token_mint,
+            user_token_account: keys.token_account(user),
+            pool_token_account: keys.pool_token_account(),
+            token_program: keys.token_program,
+            associated_token_program: associated_token::ID,
+            system_program: system_program::ID,
+        },
+        instruction::Withdraw {},
+    )
+}
+
+pub fn claim(
+    user: &Pubkey,
+    rent_payer: &Pubkey,
+    keys: &PoolKeys,
+    stake_account: &Pubkey,
+    restake: bool,
+) -> Instruction {
+    build(
+        accounts::Claim {
+            user: *user,
+            rent_payer: *rent_payer,
+            staking_pool: keys.staking_pool,
+            stake_account: *stake_account,
+            pool_checkpoints: pda::find_checkpoints_address(&keys.staking_pool).0,
+            stake_checkpoints: pda::find_checkpoints_address(stake_account).0,
+            blocklist: pda::find_blocklist_address(&keys.staking_pool, user).0,
+            token_mint: keys.token_mint,
+            user_token_account: keys.token_account(user),
+            pool_token_account: keys.pool_token_account(),
+            token_program: keys.token_program,
+            associated_token_program: associated_token::ID,
+            system_program: system_program::ID,
+        },
+        instruction::Claim { restake },
+    )
+}
+
+/// `to_pool` must hold the same token mint as `from_keys`. `proof` is for
+/// the allowlist of `to_pool`, if any.
+pub fn migrate_stake(
+    user: &Pubkey,
+    rent_payer: &Pubkey,
+    from_keys: &PoolKeys,
+    to_pool: &Pubkey,
+    from_stake_account: &Pubkey,
+    migrate_amount: u64,
+    proof: Vec<[u8; 32]>,
+) -> Instruction {
+    let to_keys = PoolKeys::new(*to_pool, from_keys.token_mint, from_keys.token_program);
+    let (to_stake_account, _) = pda::find_stake_account_address(to_pool, user);
+
+    build(
+        accounts::MigrateStake {
+            user: *user,
+            rent_payer: *rent_payer,
+            from_pool: from_keys.staking_pool,
+            to_pool: *to_pool,
+            migration_link: pda::find_migration_link_address(&from_keys.staking_pool, to_pool).0,
+            token_mint: from_keys.token_mint,
+            from_pool_token_account: from_keys.pool_token_account(),
+            to_pool_token_account: to_keys.pool_token_account(),
+            from_stake_account: *from_stake_account,
+            to_stake_account,
+            from_pool_checkpoints: pda::find_checkpoints_address(&from_keys.staking_pool).0,
+            from_stake_checkpoints: pda::find_checkpoints_address(from_stake_account).0,
+            to_pool_checkpoints: pda::find_checkpoints_address(to_pool).0,
+            to_stake_checkpoints: pda::find_checkpoints_address(&to_stake_account).0,
+            instructions_sysvar: sysvar::instructions::ID,
+            from_blocklist: pda::find_blocklist_address(&from_keys.staking_pool, user).0,
+            to_blocklist: pda::find_blocklist_address(to_pool, user).0,
+            token_program: from_keys.token_program,
+            associated_token_program: associated_token::ID,
+            system_program: system_program::ID,
+        },
+        instruction::MigrateStake {
+            migrate_amount,
+            proof,
+        },
+    )
+}
+
+/// Moves `user`'s legacy stake account in `staking_pool` to its pool scoped
+/// address, merging it into any position there.
+pub fn relocate_stake(user: &Pubkey, rent_payer: &Pubkey, staking_pool: &Pubkey) -> Instruction {
+    let (legacy_stake_account, _) = pda::find_legacy_stake_account_address(user);
+    let (stake_account, _) = pda::find_stake_account_address(staking_pool, user);
+
+    build(
+        accounts::RelocateStake {
+            user: *user,
+            rent_payer: *rent_payer,
+            staking_pool: *staking_pool,
+            legacy_stake_account,
+            stake_account,
+            stake_checkpoints: pda::find_checkpoints_address(&stake_account).0,
+            system_program: system_program::ID,
+        },
+        instruction::RelocateStake {},
+    )
+}
+
+/// Locked unbonding funds are counted towards the weight when
+/// `unstake_accounts` is given, the loyalty multiplier when `include_loyalty`
+/// is set. Only the pool scoped stake account of `user` has a weight.
+pub fn update_voter_weight_record(
+    rent_payer: &Pubkey,
+    staking_pool: &Pubkey,
+    user: &Pubkey,
+    realm: &Pubkey,
+    unstake_accounts: Option<&[Pubkey]>,
+    include_loyalty: bool,
+) -> Instruction {
+    let mut ix = build(
+        accounts::UpdateVoterWeightRecord {
+            rent_payer: *rent_payer,
+            staking_pool: *staking_pool,
+            stake_account: pda::find_stake_account_address(staking_pool, user).0,
+            realm: *realm,
+            voter_weight_record: pda::find_voter_weight_record_address(realm, staking_pool, user).0,
+            system_program: system_program::ID,
+        },
+        instruction::UpdateVoterWeightRecord {
+            include_unbonding: unstake_accounts.is_some(),
+            include_loyalty,
+        },
+    );
+    ix.accounts.extend(
+        unstake_accounts
+            .unwrap_or_default()
+            .iter()
+            .map(|unstake_account| AccountMeta::new_readonly(*unstake_account, false)),
+    );
+
+    ix
+}
+
+/// Pays out the ended epochs of an epoch mode pool. Anyone can send it.
+pub fn settle_epochs(staking_pool: &Pubkey) -> Instruction {
+    build(
+        accounts::SettleEpochs {
+            staking_pool: *staking_pool,
+        },
+        instruction::SettleEpochs {},
+    )
+}
+
+/// Emits `EventAuditPool` with the pool's surplus or deficit.
+pub fn audit_pool(keys: &PoolKeys) -> Instruction {
+    build(
+        accounts::AuditPool {
+            staking_pool: keys.staking_pool,
+            token_mint: keys.token_mint,
+            pool_token_account: keys.pool_token_account(),
+            token_program: keys.token_program,
+        },
+        instruction::AuditPool {},
+    )
+}
+
+/// `checkpoints` is a pool or stake account checkpoint buffer, see
+/// `pda::find_checkpoints_address`. The balance, in stake units, is returned
+/// as return data.
+pub fn get_balance_at(checkpoints: &Pubkey, slot: u64) -> Instruction {
+    build(
+        accounts::GetBalanceAt {
+            checkpoints: *checkpoints,
+        },
+        instruction::GetBalanceAt { slot },
+    )
+}
@@ -0,0 +1,16 @@
+//! Off-chain helpers for the staking program: PDA derivation, instruction
+//! builders, account decoding, reward previews, allowlist proofs and
+//! attestations.
+
+pub mod accounts;
+pub mod allowlist;
+pub mod attestation;
+pub mod instructions;
+pub mod pda;
+pub mod rewards;
+
+pub use staking_program::{
+    id, BalanceCheckpoints, Blocklist, ConfigParams, GrantVestingStakeParams,
+    InitializeStakingPoolParams, MigrationLink, RewardAlgorithm, ScheduledRewardChange,
+    SettledEpoch, StakeAccount, StakingPool, UnstakeAccount, VoterWeightRecord, ID,
+};
@@ -0,0 +1,167 @@
+use anchor_lang::prelude::Pubkey;
+use anchor_spl::associated_token::get_associated_token_address_with_program_id;
+use staking_program::helper;
+
+pub fn find_staking_pool_address(token_mint: &Pubkey, creator: &Pubkey, index: u8) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            helper::POOL_SEED,
+            &token_mint.to_bytes(),
+            &creator.to_bytes(),
+            &[index],
+        ],
+        &staking_program::ID,
+    )
+}
+
+pub fn find_stake_account_address(staking_pool: &Pubkey, user: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            helper::STAKE_ACCOUNT_SEED,
+            &staking_pool.to_bytes(),
+            &user.to_bytes(),
+        ],
+        &staking_program::ID,
+    )
+}
+
+/// Stake account opened before positions were scoped by pool. It holds a
+/// position in whichever pool its `staking_pool` field names.
+pub fn find_legacy_stake_account_address(user: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[helper::STAKE_ACCOUNT_SEED, &user.to_bytes()],
+        &staking_program::ID,
+    )
+}
+
+pub fn find_blocklist_address(staking_pool: &Pubkey, user: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            helper::BLOCKLIST_SEED,
+            &staking_pool.to_bytes(),
+            &user.to_bytes(),
+        ],
+        &staking_program::ID,
+    )
+}
+
+pub fn find_migration_link_address(from_pool: &Pubkey, to_pool: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            helper::MIGRATION_LINK_SEED,
+            &from_pool.to_bytes(),
+            &to_pool.to_bytes(),
+        ],
+        &staking_program::ID,
+    )
+}
+
+pub fn find_voter_weight_record_address(
+    realm: &Pubkey,
+    staking_pool: &Pubkey,
+    user: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            helper::VOTER_WEIGHT_RECORD_SEED,
+            &realm.to_bytes(),
+            &staking_pool.to_bytes(),
+            &user.to_bytes(),
+        ],
+        &staking_program::ID,
+    )
+}
+
+/// Checkpoints of a staking pool's total stake or of a stake account's amount.
+pub fn find_checkpoints_address(owner: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[helper::CHECKPOINTS_SEED, &owner.to_bytes()],
+        &staking_program::ID,
+    )
+}
+
+/// Associated token account of `owner`, used for both user and pool vaults.
+pub fn token_account_address(
+    owner: &Pubkey,
+    token_mint: &Pubkey,
+    token_program: &Pubkey,
+) -> Pubkey {
+    get_associated_token_address_with_program_id(owner, token_mint, token_program)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Seeds spelled out as in the program's account constraints, so a change
+    // to either side shows up here.
+    fn program_address(seeds: &[&[u8]]) -> Pubkey {
+        Pubkey::find_program_address(seeds, &staking_program::ID).0
+    }
+
+    #[test]
+    fn addresses_match_program_seeds() {
+        let (token_mint, creator, user, realm) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let (staking_pool, _) = find_staking_pool_address(&token_mint, &creator, 3);
+        let (other_pool, _) = find_staking_pool_address(&token_mint, &creator, 4);
+        let (stake_account, _) = find_stake_account_address(&staking_pool, &user);
+
+        assert_eq!(
+            staking_pool,
+            program_address(&[b"pool_seed", token_mint.as_ref(), creator.as_ref(), &[3]])
+        );
+        assert_eq!(
+            stake_account,
+            program_address(&[b"stake_account_seed", staking_pool.as_ref(), user.as_ref()])
+        );
+        assert_eq!(
+            find_legacy_stake_account_address(&user).0,
+            program_address(&[b"stake_account_seed", user.as_ref()])
+        );
+        assert_eq!(
+            find_blocklist_address(&staking_pool, &user).0,
+            program_address(&[b"blocklist_seed", staking_pool.as_ref(), user.as_ref()])
+        );
+        assert_eq!(
+            find_migration_link_address(&staking_pool, &other_pool).0,
+            program_address(&[
+                b"migration_link_seed",
+                staking_pool.as_ref(),
+                other_pool.as_ref()
+            ])
+        );
+        assert_eq!(
+            find_voter_weight_record_address(&realm, &staking_pool, &user).0,
+            program_address(&[
+                b"voter_weight_record_seed",
+                realm.as_ref(),
+                staking_pool.as_ref(),
+                user.as_ref()
+            ])
+        );
+        assert_eq!(
+            find_checkpoints_address(&stake_account).0,
+            program_address(&[b"checkpoints_seed", stake_account.as_ref()])
+        );
+    }
+
+    #[test]
+    fn stake_accounts_are_scoped_by_pool() {
+        let user = Pubkey::new_unique();
+        let (first_pool, second_pool) = (Pubkey::new_unique(), Pubkey::new_unique());
+
+        assert_ne!(
+            find_stake_account_address(&first_pool, &user).0,
+            find_stake_account_address(&second_pool, &user).0
+        );
+        assert_ne!(
+            find_stake_account_address(&first_pool, &user).0,
+            find_legacy_stake_account_address(&user).0
+        );
+    }
+}
@@ -0,0 +1,54 @@
+use anchor_lang::prelude::Clock;
+use anchor_lang::Result;
+use staking_program::{StakeAccount, StakingPool};
+
+/// Reward emitted by `pool` over `time_diff` seconds, before capping by
+/// `undistributed_reward`.
+pub fn calc_new_reward(pool: &StakingPool, time_diff: u64) -> Result<u64> {
+    Ok(pool.calc_new_reward(time_diff)?.0)
+}
+
+/// Returns `pool` as it would look after `update_pool` ran at `clock`.
+pub fn preview_update_pool(pool: &StakingPool, clock: &Clock) -> Result<StakingPool> {
+    let mut pool = pool.clone();
+    pool.update_pool_with_clock(clock)?;
+
+    Ok(pool)
+}
+
+/// Returns `stake_account` as it would look after `update_reward` ran
+/// against `pool`.
+pub fn preview_update_reward(
+    stake_account: &StakeAccount,
+    pool: &StakingPool,
+) -> Result<StakeAccount> {
+    let mut stake_account = stake_account.clone();
+    stake_account.update_reward(pool)?;
+
+    Ok(stake_account)
+}
+
+/// Reward `claim` would pay out to `stake_account` at `clock`.
+pub fn pending_reward(
+    pool: &StakingPool,
+    stake_account: &StakeAccount,
+    clock: &Clock,
+) -> Result<u128> {
+    let pool = preview_update_pool(pool, clock)?;
+
+    Ok(preview_update_reward(stake_account, &pool)?.reward)
+}
+
+/// Unclaimed rewards of all `stake_accounts` of `pool`, as of its current
+/// `reward_per_share` after `StakingPool::migrate`, which is returned along.
+pub fn owed_rewards(pool: &StakingPool, stake_accounts: &[StakeAccount]) -> Result<(u128, u128)> {
+    let mut pool = pool.clone();
+    pool.migrate()?;
+
+    let mut owed_rewards = 0;
+    for stake_account in stake_accounts {
+        owed_rewards += preview_update_reward(stake_account, &pool)?.reward;
+    }
+
+    Ok((owed_rewards, pool.reward_per_share))
+}
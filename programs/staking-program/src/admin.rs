@@ -1,9 +1,10 @@
-use crate::{Errors, RewardAlgorithm, StakingPool};
+use crate::{
+    helper, BalanceCheckpoints, Blocklist, Errors, MigrationLink, RewardAlgorithm,
+    ScheduledRewardChange, SettledEpoch, StakeAccount, StakingPool,
+};
 use anchor_lang::prelude::*;
 use anchor_spl::associated_token::AssociatedToken;
-use anchor_spl::token_interface::{
-    transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked,
-};
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked};
 
 #[derive(Accounts)]
 pub struct TransferAdmin<'info> {
@@ -63,6 +64,19 @@ pub struct ConfigParams {
     pub reward_rate: Option<u64>,
     pub unbonding_seconds: Option<u64>,
     pub reward_algorithm: Option<RewardAlgorithm>,
+    /// `Pubkey::default()` disables slashing.
+    pub slasher: Option<Pubkey>,
+    pub min_stake_duration: Option<u64>,
+    /// Scaled by `helper::LOYALTY_MULTIPLIER_BASE`, zero disables it.
+    pub max_loyalty_multiplier: Option<u64>,
+    pub loyalty_ramp_seconds: Option<u64>,
+    /// Applies to unstake accounts created afterwards.
+    pub linear_unbonding: Option<bool>,
+    /// Rotates the allowlist; all zero opens staking to anyone. Existing
+    /// positions are not affected.
+    pub allowlist_root: Option<[u8; 32]>,
+    /// `Pubkey::default()` stops requiring attestations.
+    pub attestor: Option<Pubkey>,
 }
 
 impl<'info> Config<'info> {
@@ -82,12 +96,266 @@ impl<'info> Config<'info> {
             msg!("unbonding_seconds: {}", unbonding_seconds);
         }
         if let Some(reward_algorithm) = config_params.reward_algorithm {
+            reward_algorithm.check()?;
             self.staking_pool.update_pool()?;
 
-            self.staking_pool.reward_algorithm = reward_algorithm.clone();
+            self.staking_pool.set_reward_algorithm(reward_algorithm);
             msg!("reward_algorithm: {:?}", reward_algorithm);
         }
+        if let Some(slasher) = config_params.slasher {
+            self.staking_pool.slasher = slasher;
+            msg!("slasher: {}", slasher);
+        }
+        if let Some(min_stake_duration) = config_params.min_stake_duration {
+            self.staking_pool.min_stake_duration = min_stake_duration;
+            msg!("min_stake_duration: {}", min_stake_duration);
+        }
+        // Positions pick up a new multiplier the next time they are synced.
+        if let Some(max_loyalty_multiplier) = config_params.max_loyalty_multiplier {
+            require!(
+                max_loyalty_multiplier == 0
+                    || max_loyalty_multiplier >= helper::LOYALTY_MULTIPLIER_BASE,
+                Errors::ParamsNotMatch
+            );
+            self.staking_pool.update_pool()?;
+
+            self.staking_pool.max_loyalty_multiplier = max_loyalty_multiplier;
+            msg!("max_loyalty_multiplier: {}", max_loyalty_multiplier);
+        }
+        if let Some(loyalty_ramp_seconds) = config_params.loyalty_ramp_seconds {
+            self.staking_pool.update_pool()?;
+
+            self.staking_pool.loyalty_ramp_seconds = loyalty_ramp_seconds;
+            msg!("loyalty_ramp_seconds: {}", loyalty_ramp_seconds);
+        }
+        if let Some(linear_unbonding) = config_params.linear_unbonding {
+            self.staking_pool.linear_unbonding = linear_unbonding;
+            msg!("linear_unbonding: {}", linear_unbonding);
+        }
+        if let Some(allowlist_root) = config_params.allowlist_root {
+            self.staking_pool.allowlist_root = allowlist_root;
+            msg!("allowlist_root: {:?}", allowlist_root);
+        }
+        if let Some(attestor) = config_params.attestor {
+            self.staking_pool.attestor = attestor;
+            msg!("attestor: {}", attestor);
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+pub struct ScheduleRewardChange<'info> {
+    pub admin: Signer<'info>,
+
+    #[account(mut)]
+    pub rent_payer: Signer<'info>,
+
+    #[account(
+        mut,
+        has_one = admin @ Errors::AdminNotMatch,
+        realloc = StakingPool::SPACE,
+        realloc::payer = rent_payer,
+        realloc::zero = false,
+    )]
+    pub staking_pool: Box<Account<'info, StakingPool>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[event]
+pub struct EventScheduleRewardChange {
+    pub staking_pool: Pubkey,
+    pub effective_timestamp: u64,
+    pub reward_rate: u64,
+    pub reward_algorithm: RewardAlgorithm,
+}
+
+impl<'info> ScheduleRewardChange<'info> {
+    /// Not available in epoch mode, where a scheduled per second rate would be
+    /// paid per epoch.
+    pub fn process(&mut self, change: ScheduledRewardChange) -> Result<()> {
+        require!(!self.staking_pool.is_epoch_mode(), Errors::ParamsNotMatch);
+        change.reward_algorithm.check()?;
+        require_gt!(
+            change.effective_timestamp,
+            Clock::get()?.unix_timestamp as u64,
+            Errors::ParamsNotMatch
+        );
+
+        let reward_schedule = &mut self.staking_pool.reward_schedule;
+        require_gt!(
+            helper::REWARD_SCHEDULE_CAPACITY,
+            reward_schedule.len(),
+            Errors::RewardScheduleFull
+        );
+        let index = match reward_schedule
+            .binary_search_by_key(&change.effective_timestamp, |entry| {
+                entry.effective_timestamp
+            }) {
+            Ok(_) => return err!(Errors::ParamsNotMatch),
+            Err(index) => index,
+        };
+        reward_schedule.insert(index, change);
+
+        emit!(EventScheduleRewardChange {
+            staking_pool: self.staking_pool.key(),
+            effective_timestamp: change.effective_timestamp,
+            reward_rate: change.reward_rate,
+            reward_algorithm: change.reward_algorithm,
+        });
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+pub struct CancelRewardChange<'info> {
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        has_one = admin @ Errors::AdminNotMatch
+    )]
+    pub staking_pool: Box<Account<'info, StakingPool>>,
+}
+
+#[event]
+pub struct EventCancelRewardChange {
+    pub staking_pool: Pubkey,
+    pub effective_timestamp: u64,
+}
+
+impl<'info> CancelRewardChange<'info> {
+    /// Drops the change scheduled at `effective_timestamp`, if not yet
+    /// applied.
+    pub fn process(&mut self, effective_timestamp: u64) -> Result<()> {
+        // Changes already due belong to the past and are applied instead.
+        self.staking_pool.update_pool()?;
+
+        let reward_schedule = &mut self.staking_pool.reward_schedule;
+        let index = reward_schedule
+            .iter()
+            .position(|entry| entry.effective_timestamp == effective_timestamp)
+            .ok_or_else(|| error!(Errors::ParamsNotMatch))?;
+        reward_schedule.remove(index);
 
+        emit!(EventCancelRewardChange {
+            staking_pool: self.staking_pool.key(),
+            effective_timestamp,
+        });
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+pub struct EnableEpochMode<'info> {
+    pub admin: Signer<'info>,
+
+    #[account(mut)]
+    pub rent_payer: Signer<'info>,
+
+    #[account(
+        mut,
+        has_one = admin @ Errors::AdminNotMatch,
+        realloc = StakingPool::SPACE,
+        realloc::payer = rent_payer,
+        realloc::zero = false,
+    )]
+    pub staking_pool: Box<Account<'info, StakingPool>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[event]
+pub struct EventEnableEpochMode {
+    pub staking_pool: Pubkey,
+    pub epoch_length: u64,
+    pub reward_rate: u64,
+    pub first_epoch: u64,
+}
+
+impl<'info> EnableEpochMode<'info> {
+    /// Continuous rewards are accrued up to now, then the current epoch is
+    /// the first paid `reward_rate` per epoch. Current stakers are eligible
+    /// for it. Changes still scheduled would be read as per epoch rates, so
+    /// they must be cancelled first.
+    pub fn process(&mut self, epoch_length: u64, reward_rate: u64) -> Result<()> {
+        require_gt!(epoch_length, 0, Errors::ParamsNotMatch);
+        require!(!self.staking_pool.is_epoch_mode(), Errors::ParamsNotMatch);
+
+        self.staking_pool.update_pool()?;
+        require!(
+            self.staking_pool.reward_schedule.is_empty(),
+            Errors::ParamsNotMatch
+        );
+
+        self.staking_pool.epoch_length = epoch_length;
+        self.staking_pool.reward_rate = reward_rate;
+        let first_epoch = self.staking_pool.epoch_at(&Clock::get()?);
+        require_gt!(first_epoch, 0, Errors::ParamsNotMatch);
+
+        self.staking_pool.pending_weight = 0;
+        self.staking_pool.epoch_history = vec![SettledEpoch {
+            epoch: first_epoch - 1,
+            reward_per_share: self.staking_pool.reward_per_share,
+        }];
+
+        emit!(EventEnableEpochMode {
+            staking_pool: self.staking_pool.key(),
+            epoch_length,
+            reward_rate,
+            first_epoch,
+        });
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+pub struct BackfillOwedRewards<'info> {
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        has_one = admin @ Errors::AdminNotMatch
+    )]
+    pub staking_pool: Box<Account<'info, StakingPool>>,
+}
+
+#[event]
+pub struct EventBackfillOwedRewards {
+    pub staking_pool: Pubkey,
+    pub reward_per_share: u128,
+    pub total_owed_rewards: u64,
+}
+
+impl<'info> BackfillOwedRewards<'info> {
+    /// Completes `total_owed_rewards` of a pool created before it was
+    /// tracked with `owed_rewards`, the unclaimed rewards of all its stake
+    /// accounts as of `reward_per_share`, after `StakingPool::migrate`. The
+    /// pool must not have been updated since, so the sum can be checked
+    /// off-chain against the accounts at the slot of this instruction.
+    pub fn process(&mut self, owed_rewards: u64, reward_per_share: u128) -> Result<()> {
+        require!(
+            !self.staking_pool.owed_rewards_tracked,
+            Errors::ParamsNotMatch
+        );
+        self.staking_pool.migrate()?;
+        require!(
+            self.staking_pool.reward_per_share == reward_per_share,
+            Errors::ParamsNotMatch
+        );
+
+        // Tracked owed rewards are counted before per-account rounding.
+        self.staking_pool.total_owed_rewards =
+            self.staking_pool.total_owed_rewards.max(owed_rewards);
+        self.staking_pool.owed_rewards_tracked = true;
+
+        emit!(EventBackfillOwedRewards {
+            staking_pool: self.staking_pool.key(),
+            reward_per_share,
+            total_owed_rewards: self.staking_pool.total_owed_rewards,
+        });
         Ok(())
     }
 }
@@ -115,6 +383,9 @@ pub struct AddRewards<'info> {
     )]
     pub pool_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
 
+    #[account(
+        address = staking_pool.token_mint @Errors::TokenMintAccountNotMatch
+    )]
     pub token_mint: Box<InterfaceAccount<'info, Mint>>,
 
     pub token_program: Interface<'info, TokenInterface>,
@@ -122,8 +393,16 @@ pub struct AddRewards<'info> {
 }
 
 impl<'info> AddRewards<'info> {
-    pub fn process(&mut self, amount: u64) -> Result<()> {
+    pub fn process(
+        &mut self,
+        amount: u64,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
         require_gt!(amount, 0, Errors::ParamsNotMatch);
+        helper::check_transfer_hook_accounts(
+            self.staking_pool.extension_policy,
+            remaining_accounts,
+        )?;
 
         self.staking_pool.update_pool()?;
 
@@ -135,9 +414,11 @@ impl<'info> AddRewards<'info> {
                 to: self.pool_token_account.to_account_info(),
                 authority: self.admin.to_account_info(),
             },
-        );
-        transfer_checked(
+        )
+        .with_remaining_accounts(remaining_accounts.to_vec());
+        let amount = helper::transfer_checked_received(
             transfer_to_pool_cpi_context,
+            &mut self.pool_token_account,
             amount,
             self.token_mint.decimals,
         )?;
@@ -149,3 +430,356 @@ impl<'info> AddRewards<'info> {
         Ok(())
     }
 }
+
+#[derive(Accounts)]
+#[instruction(beneficiary: Pubkey)]
+pub struct GrantVestingStake<'info> {
+    pub admin: Signer<'info>,
+
+    #[account(mut)]
+    pub rent_payer: Signer<'info>,
+
+    #[account(
+        mut,
+        has_one = admin @ Errors::AdminNotMatch
+    )]
+    pub staking_pool: Box<Account<'info, StakingPool>>,
+
+    #[account(
+        address = staking_pool.token_mint @Errors::TokenMintAccountNotMatch
+    )]
+    pub token_mint: Box<InterfaceAccount<'info, Mint>>,
+
+    #[account(
+        mut,
+        associated_token::mint = token_mint,
+        associated_token::authority = admin,
+        associated_token::token_program = token_program,
+    )]
+    pub admin_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        associated_token::mint = token_mint,
+        associated_token::authority = staking_pool,
+        associated_token::token_program = token_program,
+    )]
+    pub pool_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        init_if_needed,
+        space = StakeAccount::SPACE,
+        payer = rent_payer,
+        rent_exempt = enforce,
+        seeds = [
+            helper::STAKE_ACCOUNT_SEED,
+            &staking_pool.key().to_bytes(),
+            &beneficiary.to_bytes(),
+        ],
+        bump,
+    )]
+    pub stake_account: Box<Account<'info, StakeAccount>>,
+
+    #[account(
+        init_if_needed,
+        space = 8 + std::mem::size_of::<BalanceCheckpoints>(),
+        payer = rent_payer,
+        rent_exempt = enforce,
+        seeds = [
+            helper::CHECKPOINTS_SEED,
+            &staking_pool.key().to_bytes(),
+        ],
+        bump,
+    )]
+    pub pool_checkpoints: Box<Account<'info, BalanceCheckpoints>>,
+
+    #[account(
+        init_if_needed,
+        space = 8 + std::mem::size_of::<BalanceCheckpoints>(),
+        payer = rent_payer,
+        rent_exempt = enforce,
+        seeds = [
+            helper::CHECKPOINTS_SEED,
+            &stake_account.key().to_bytes(),
+        ],
+        bump,
+    )]
+    pub stake_checkpoints: Box<Account<'info, BalanceCheckpoints>>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, AnchorSerialize, AnchorDeserialize)]
+pub struct GrantVestingStakeParams {
+    pub amount: u64,
+    pub start_timestamp: u64,
+    pub cliff_timestamp: u64,
+    pub end_timestamp: u64,
+}
+
+#[event]
+pub struct EventGrantVestingStake {
+    pub beneficiary: Pubkey,
+    pub amount: u64,
+    pub start_timestamp: u64,
+    pub cliff_timestamp: u64,
+    pub end_timestamp: u64,
+    pub staking_pool: Pubkey,
+}
+
+impl<'info> GrantVestingStake<'info> {
+    pub fn process(
+        &mut self,
+        beneficiary: Pubkey,
+        params: GrantVestingStakeParams,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        require_gt!(params.amount, 0, Errors::ParamsNotMatch);
+        require_gt!(
+            params.end_timestamp,
+            params.start_timestamp,
+            Errors::ParamsNotMatch
+        );
+        require!(
+            params.start_timestamp <= params.cliff_timestamp
+                && params.cliff_timestamp <= params.end_timestamp,
+            Errors::ParamsNotMatch
+        );
+        helper::check_transfer_hook_accounts(
+            self.staking_pool.extension_policy,
+            remaining_accounts,
+        )?;
+
+        // A position holds a single schedule, so a new grant waits until the
+        // previous one fully vested.
+        let current_time = Clock::get()?.unix_timestamp as u64;
+        require_eq!(
+            self.stake_account.locked_amount(current_time),
+            0,
+            Errors::VestingScheduleActive
+        );
+
+        self.staking_pool.update_pool()?;
+
+        let transfer_to_pool_cpi_context = CpiContext::new(
+            self.token_program.to_account_info(),
+            TransferChecked {
+                from: self.admin_token_account.to_account_info(),
+                mint: self.token_mint.to_account_info(),
+                to: self.pool_token_account.to_account_info(),
+                authority: self.admin.to_account_info(),
+            },
+        )
+        .with_remaining_accounts(remaining_accounts.to_vec());
+        let amount = helper::transfer_checked_received(
+            transfer_to_pool_cpi_context,
+            &mut self.pool_token_account,
+            params.amount,
+            self.token_mint.decimals,
+        )?;
+
+        let previous_total_stake = self.staking_pool.total_stake;
+        let previous_amount = self.stake_account.amount;
+
+        let staking_pool_key = self.staking_pool.key();
+        let stake_units = self.stake_account.deposit(
+            &mut self.staking_pool,
+            staking_pool_key,
+            beneficiary,
+            amount,
+        )?;
+
+        self.stake_account.vesting_amount = stake_units;
+        self.stake_account.vesting_start_timestamp = params.start_timestamp;
+        self.stake_account.vesting_cliff_timestamp = params.cliff_timestamp;
+        self.stake_account.vesting_end_timestamp = params.end_timestamp;
+
+        let slot = Clock::get()?.slot;
+        self.pool_checkpoints.record(
+            self.staking_pool.key(),
+            slot,
+            previous_total_stake,
+            self.staking_pool.total_stake,
+        );
+        self.stake_checkpoints.record(
+            self.stake_account.key(),
+            slot,
+            previous_amount,
+            self.stake_account.amount,
+        );
+
+        emit!(EventGrantVestingStake {
+            beneficiary,
+            amount,
+            start_timestamp: params.start_timestamp,
+            cliff_timestamp: params.cliff_timestamp,
+            end_timestamp: params.end_timestamp,
+            staking_pool: self.staking_pool.key(),
+        });
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+pub struct ApproveMigrationLink<'info> {
+    pub admin: Signer<'info>,
+
+    #[account(mut)]
+    pub rent_payer: Signer<'info>,
+
+    pub from_pool: Box<Account<'info, StakingPool>>,
+
+    #[account(
+        constraint = to_pool.token_mint == from_pool.token_mint @Errors::TokenMintAccountNotMatch,
+        constraint = to_pool.key() != from_pool.key() @Errors::ParamsNotMatch,
+    )]
+    pub to_pool: Box<Account<'info, StakingPool>>,
+
+    #[account(
+        init_if_needed,
+        space = 8 + std::mem::size_of::<MigrationLink>(),
+        payer = rent_payer,
+        rent_exempt = enforce,
+        seeds = [
+            helper::MIGRATION_LINK_SEED,
+            &from_pool.key().to_bytes(),
+            &to_pool.key().to_bytes(),
+        ],
+        bump,
+    )]
+    pub migration_link: Box<Account<'info, MigrationLink>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[event]
+pub struct EventApproveMigrationLink {
+    pub admin: Pubkey,
+    pub from_pool: Pubkey,
+    pub to_pool: Pubkey,
+    pub approved: bool,
+}
+
+impl<'info> ApproveMigrationLink<'info> {
+    /// Sets the approval of whichever side `admin` administers, or both when
+    /// it administers both pools. `approved = false` revokes it.
+    pub fn process(&mut self, approved: bool) -> Result<()> {
+        let admin = self.admin.key();
+        require!(
+            admin == self.from_pool.admin || admin == self.to_pool.admin,
+            Errors::AdminNotMatch
+        );
+
+        self.migration_link.from_pool = self.from_pool.key();
+        self.migration_link.to_pool = self.to_pool.key();
+        if admin == self.from_pool.admin {
+            self.migration_link.from_pool_approved = approved;
+        }
+        if admin == self.to_pool.admin {
+            self.migration_link.to_pool_approved = approved;
+        }
+
+        emit!(EventApproveMigrationLink {
+            admin,
+            from_pool: self.from_pool.key(),
+            to_pool: self.to_pool.key(),
+            approved,
+        });
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+#[instruction(user: Pubkey)]
+pub struct SetBlocklist<'info> {
+    pub admin: Signer<'info>,
+
+    #[account(mut)]
+    pub rent_payer: Signer<'info>,
+
+    #[account(
+        has_one = admin @ Errors::AdminNotMatch
+    )]
+    pub staking_pool: Box<Account<'info, StakingPool>>,
+
+    #[account(
+        init_if_needed,
+        space = 8 + std::mem::size_of::<Blocklist>(),
+        payer = rent_payer,
+        rent_exempt = enforce,
+        seeds = [
+            helper::BLOCKLIST_SEED,
+            &staking_pool.key().to_bytes(),
+            &user.to_bytes(),
+        ],
+        bump,
+    )]
+    pub blocklist: Box<Account<'info, Blocklist>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[event]
+pub struct EventSetBlocklist {
+    pub staking_pool: Pubkey,
+    pub user: Pubkey,
+    pub reason: u16,
+    pub frozen: bool,
+}
+
+impl<'info> SetBlocklist<'info> {
+    /// Blocks `user`, or updates the reason and freeze of an existing block.
+    pub fn process(&mut self, user: Pubkey, reason: u16, frozen: bool) -> Result<()> {
+        self.blocklist.staking_pool = self.staking_pool.key();
+        self.blocklist.user = user;
+        self.blocklist.reason = reason;
+        self.blocklist.frozen = frozen;
+
+        emit!(EventSetBlocklist {
+            staking_pool: self.staking_pool.key(),
+            user,
+            reason,
+            frozen,
+        });
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+pub struct ClearBlocklist<'info> {
+    pub admin: Signer<'info>,
+
+    /// CHECK: receives the blocklist rent.
+    #[account(mut)]
+    pub rent_receiver: UncheckedAccount<'info>,
+
+    #[account(
+        has_one = admin @ Errors::AdminNotMatch
+    )]
+    pub staking_pool: Box<Account<'info, StakingPool>>,
+
+    #[account(
+        mut,
+        close = rent_receiver,
+        has_one = staking_pool @ Errors::ParamsNotMatch,
+    )]
+    pub blocklist: Box<Account<'info, Blocklist>>,
+}
+
+#[event]
+pub struct EventClearBlocklist {
+    pub staking_pool: Pubkey,
+    pub user: Pubkey,
+}
+
+impl<'info> ClearBlocklist<'info> {
+    pub fn process(&mut self) -> Result<()> {
+        emit!(EventClearBlocklist {
+            staking_pool: self.staking_pool.key(),
+            user: self.blocklist.user,
+        });
+        Ok(())
+    }
+}
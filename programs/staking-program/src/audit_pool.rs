@@ -0,0 +1,63 @@
+use crate::{Errors, StakingPool};
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+
+#[derive(Accounts)]
+pub struct AuditPool<'info> {
+    #[account(mut)]
+    pub staking_pool: Box<Account<'info, StakingPool>>,
+
+    #[account(
+        address = staking_pool.token_mint @Errors::TokenMintAccountNotMatch
+    )]
+    pub token_mint: Box<InterfaceAccount<'info, Mint>>,
+
+    #[account(
+        associated_token::mint = token_mint,
+        associated_token::authority = staking_pool,
+        associated_token::token_program = token_program,
+    )]
+    pub pool_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[event]
+pub struct EventAuditPool {
+    pub staking_pool: Pubkey,
+    pub vault_balance: u64,
+    pub total_stake: u64,
+    pub total_unbonding: u64,
+    pub total_owed_rewards: u64,
+    pub undistributed_reward: u64,
+    /// `None` until the pool's `owed_rewards_tracked`, since rewards owed
+    /// from before would show up as surplus and could hide a deficit.
+    pub surplus: Option<u64>,
+    pub deficit: Option<u64>,
+}
+
+impl<'info> AuditPool<'info> {
+    /// Compares the vault balance against every tracked bucket. Owed rewards
+    /// are counted before per-account rounding, so they are never
+    /// below what stakers can claim.
+    pub fn process(&mut self) -> Result<()> {
+        self.staking_pool.update_pool()?;
+
+        let vault_balance = self.pool_token_account.amount;
+        let tracked_balance = self.staking_pool.tracked_balance()?;
+        let known = self.staking_pool.owed_rewards_tracked;
+
+        emit!(EventAuditPool {
+            staking_pool: self.staking_pool.key(),
+            vault_balance,
+            total_stake: self.staking_pool.total_stake_tokens()?,
+            total_unbonding: self.staking_pool.total_unbonding,
+            total_owed_rewards: self.staking_pool.total_owed_rewards,
+            undistributed_reward: self.staking_pool.undistributed_reward,
+            surplus: known.then(|| vault_balance.saturating_sub(tracked_balance)),
+            deficit: known.then(|| tracked_balance.saturating_sub(vault_balance)),
+        });
+
+        Ok(())
+    }
+}
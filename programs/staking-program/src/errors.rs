@@ -46,4 +46,46 @@ pub enum Errors {
 
     #[msg("Pending admin not match")]
     PendingAdminNotMatch,
+
+    #[msg("Token mint extension not allowed")]
+    MintExtensionNotAllowed,
+
+    #[msg("Checkpoint not found")]
+    CheckpointNotFound,
+
+    #[msg("Stake amount still locked by vesting")]
+    StakeAmountLocked,
+
+    #[msg("Vesting schedule still active")]
+    VestingScheduleActive,
+
+    #[msg("Slasher not match")]
+    SlasherNotMatch,
+
+    #[msg("Slash amount too high")]
+    SlashAmountTooHigh,
+
+    #[msg("Minimum stake duration not reached")]
+    StakeDurationNotReached,
+
+    #[msg("Migration link not approved")]
+    MigrationNotApproved,
+
+    #[msg("User not on the pool allowlist")]
+    NotAllowlisted,
+
+    #[msg("Invalid attestation")]
+    InvalidAttestation,
+
+    #[msg("Attestation expired")]
+    AttestationExpired,
+
+    #[msg("User is blocked in the pool")]
+    UserBlocked,
+
+    #[msg("Stake account is frozen")]
+    StakeAccountFrozen,
+
+    #[msg("Reward schedule is full")]
+    RewardScheduleFull,
 }
@@ -1,6 +1,662 @@
-pub const POOL_SEED: &'static [u8] = b"pool_seed";
-pub const STAKE_ACCOUNT_SEED: &'static [u8] = b"stake_account_seed";
+use crate::Errors;
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::sysvar::instructions::{
+    load_current_index_checked, load_instruction_at_checked,
+};
+use anchor_lang::solana_program::{ed25519_program, keccak};
+use anchor_spl::token_2022::spl_token_2022;
+use anchor_spl::token_2022::spl_token_2022::extension::{
+    non_transferable::NonTransferable, permanent_delegate::PermanentDelegate, transfer_hook,
+    BaseStateWithExtensions, StateWithExtensions,
+};
+use anchor_spl::token_interface::{Mint, TokenAccount, TransferChecked};
+
+pub const POOL_SEED: &[u8] = b"pool_seed";
+pub const STAKE_ACCOUNT_SEED: &[u8] = b"stake_account_seed";
+pub const MIGRATION_LINK_SEED: &[u8] = b"migration_link_seed";
+pub const BLOCKLIST_SEED: &[u8] = b"blocklist_seed";
+pub const VOTER_WEIGHT_RECORD_SEED: &[u8] = b"voter_weight_record_seed";
+pub const CHECKPOINTS_SEED: &[u8] = b"checkpoints_seed";
 
 pub const REWARD_CALC_BASE: u128 = 1_000_000_000_000;
 
+/// Scale of `StakingPool::reward_per_share`. Pools created with the former
+/// `REWARD_CALC_BASE` scale are rescaled by `update_pool`.
+pub const REWARD_PER_SHARE_BASE: u128 = 1_000_000_000_000_000_000;
+
+/// Current `StakingPool::version`.
+pub const POOL_VERSION: u8 = 2;
+
+/// Current `StakeAccount::version`.
+pub const STAKE_ACCOUNT_VERSION: u8 = 2;
+
+/// Scale of `StakingPool::max_loyalty_multiplier`, i.e. 1x.
+pub const LOYALTY_MULTIPLIER_BASE: u64 = 10_000;
+
+/// Scale of `StakingPool::stake_scale` and `unbonding_scale`, i.e. 1.0.
+pub const SLASH_SCALE_BASE: u128 = 1_000_000_000_000_000_000;
+
+/// Scale of annual rates in basis points, i.e. 100%.
+pub const BPS_BASE: u64 = 10_000;
+
+pub const SECONDS_PER_YEAR: u64 = 365 * 24 * 60 * 60;
+
 pub const DEFAULT_MIN_STAKE_AMOUNT: u64 = 1_000;
+
+pub const CHECKPOINTS_CAPACITY: usize = 32;
+
+pub const REWARD_SCHEDULE_CAPACITY: usize = 8;
+
+pub const EPOCH_HISTORY_CAPACITY: usize = 32;
+
+/// `StakingPool::epoch_length` following the Solana `EpochSchedule`.
+pub const SOLANA_EPOCHS: u64 = u64::MAX;
+
+/// Token-2022 mint features a pool admin may opt into through
+/// `extension_policy`.
+pub const ALLOW_PERMANENT_DELEGATE: u8 = 1 << 0;
+pub const ALLOW_FREEZE_AUTHORITY: u8 = 1 << 1;
+pub const ALLOW_TRANSFER_HOOK: u8 = 1 << 2;
+
+/// Rejects Token-2022 mints whose authorities could move, freeze or block
+/// the pool vault unless `extension_policy` opts into them. Classic SPL Token
+/// mints are accepted as they are, freeze authority included.
+pub fn check_mint_extensions(mint: &InterfaceAccount<Mint>, extension_policy: u8) -> Result<()> {
+    let mint_info = mint.to_account_info();
+    if *mint_info.owner != spl_token_2022::ID {
+        return Ok(());
+    }
+
+    if mint.freeze_authority.is_some() {
+        require!(
+            extension_policy & ALLOW_FREEZE_AUTHORITY != 0,
+            Errors::MintExtensionNotAllowed
+        );
+    }
+
+    let mint_data = mint_info.try_borrow_data()?;
+    let mint_state = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&mint_data)?;
+
+    // Non-transferable tokens can never be withdrawn from the vault.
+    require!(
+        mint_state.get_extension::<NonTransferable>().is_err(),
+        Errors::MintExtensionNotAllowed
+    );
+
+    if let Ok(permanent_delegate) = mint_state.get_extension::<PermanentDelegate>() {
+        if Option::<Pubkey>::from(permanent_delegate.delegate).is_some() {
+            require!(
+                extension_policy & ALLOW_PERMANENT_DELEGATE != 0,
+                Errors::MintExtensionNotAllowed
+            );
+        }
+    }
+
+    if transfer_hook::get_program_id(&mint_state).is_some() {
+        require!(
+            extension_policy & ALLOW_TRANSFER_HOOK != 0,
+            Errors::MintExtensionNotAllowed
+        );
+    }
+
+    Ok(())
+}
+
+/// Extra accounts are only forwarded to transfer hooks the pool opted into.
+pub fn check_transfer_hook_accounts(
+    extension_policy: u8,
+    remaining_accounts: &[AccountInfo],
+) -> Result<()> {
+    if !remaining_accounts.is_empty() {
+        require!(
+            extension_policy & ALLOW_TRANSFER_HOOK != 0,
+            Errors::RemainingAccountsNotMatch
+        );
+    }
+
+    Ok(())
+}
+
+/// Allowlist Merkle leaf of `user`. Leaves and nodes are domain separated so
+/// a node can't be passed off as a leaf.
+pub fn allowlist_leaf(user: &Pubkey) -> [u8; 32] {
+    keccak::hashv(&[&[0u8], user.as_ref()]).to_bytes()
+}
+
+/// Parent of two allowlist Merkle nodes, hashed in sorted order so proofs
+/// don't need to encode the side of each sibling.
+pub fn allowlist_node(a: &[u8; 32], b: &[u8; 32]) -> [u8; 32] {
+    let (left, right) = if a <= b { (a, b) } else { (b, a) };
+    keccak::hashv(&[&[1u8], left, right]).to_bytes()
+}
+
+/// `2^(-2^-i)` in Q64.64 for `i` in `1..=40`, the factors of `exp2_neg`.
+const EXP2_NEG_POW2_Q64: [u128; 40] = [
+    13043817825332782212,
+    15511800964685064948,
+    16915738899553466670,
+    17664662643191237676,
+    18051468387014017850,
+    18248035989933441396,
+    18347121020861646923,
+    18396865112328554661,
+    18421787711448657617,
+    18434261669329232139,
+    18440501815349552981,
+    18443622680442407997,
+    18445183311048607332,
+    18445963675871538003,
+    18446353870663572145,
+    18446548971154807802,
+    18446646522174239825,
+    18446695297877410579,
+    18446719685777359790,
+    18446731879739425374,
+    18446737976723480912,
+    18446741025216264368,
+    18446742549462845018,
+    18446743311586182573,
+    18446743692647863158,
+    18446743883178706403,
+    18446743978444128763,
+    18446744026076840128,
+    18446744049893195856,
+    18446744061801373732,
+    18446744067755462673,
+    18446744070732507144,
+    18446744072221029380,
+    18446744072965290498,
+    18446744073337421057,
+    18446744073523486336,
+    18446744073616518976,
+    18446744073663035296,
+    18446744073686293456,
+    18446744073697922536,
+];
+
+/// `1 / ln(2)` in Q64.64.
+const INV_LN2_Q64: u128 = 26613026195688644983;
+
+/// `(a * b) >> 64`, or `None` on overflow.
+pub fn mul_shr_64(a: u128, b: u128) -> Option<u128> {
+    let (a_hi, a_lo) = (a >> 64, a & u64::MAX as u128);
+    let (b_hi, b_lo) = (b >> 64, b & u64::MAX as u128);
+
+    a_hi.checked_mul(b_hi)?
+        .checked_mul(1 << 64)?
+        .checked_add(a_hi * b_lo)?
+        .checked_add(a_lo * b_hi)?
+        .checked_add((a_lo * b_lo) >> 64)
+}
+
+/// `a * b / divisor` through a 192-bit product, or `None` when the quotient
+/// overflows `u128` or `divisor` is zero.
+pub fn mul_div(a: u128, b: u64, divisor: u64) -> Option<u128> {
+    let low = (a & u64::MAX as u128) * (b as u128);
+    let high = (a >> 64) * (b as u128);
+    let middle = (low >> 64) + (high & u64::MAX as u128);
+    let limbs = [
+        (high >> 64) + (middle >> 64),
+        middle & u64::MAX as u128,
+        low & u64::MAX as u128,
+    ];
+
+    let mut quotient: u128 = 0;
+    let mut remainder: u128 = 0;
+    for limb in limbs {
+        let value = (remainder << 64) | limb;
+        quotient = quotient
+            .checked_mul(1 << 64)?
+            .checked_add(value.checked_div(divisor as u128)?)?;
+        remainder = value % (divisor as u128);
+    }
+
+    Some(quotient)
+}
+
+/// Token amount `weight` earns at `reward_per_share`, which is scaled by
+/// `REWARD_PER_SHARE_BASE`.
+pub fn reward_for(weight: u64, reward_per_share: u128) -> Result<u128> {
+    mul_div(reward_per_share, weight, REWARD_PER_SHARE_BASE as u64)
+        .ok_or_else(|| error!(Errors::CalculationFail))
+}
+
+/// `2^-(numerator / denominator)` in Q64.64, for `numerator < denominator`.
+fn exp2_neg(numerator: u64, denominator: u64) -> u128 {
+    let fraction = ((numerator as u128) << 64) / (denominator as u128);
+
+    EXP2_NEG_POW2_Q64
+        .iter()
+        .enumerate()
+        .filter(|(bit, _)| fraction & (1 << (63 - bit)) != 0)
+        .fold(1 << 64, |result, (_, factor)| (result * factor) >> 64)
+}
+
+/// Total emission `elapsed` seconds after the anchor of a decaying
+/// schedule starting at `reward_rate` per second and halving every
+/// `half_life_seconds`, either in steps or continuously. Rewards between two
+/// times are the difference of this, so rounding never accumulates.
+pub fn decayed_emission(
+    reward_rate: u64,
+    half_life_seconds: u64,
+    continuous: bool,
+    elapsed: u64,
+) -> Result<u128> {
+    let halvings = elapsed / half_life_seconds;
+    let rest = elapsed % half_life_seconds;
+
+    if !continuous {
+        // r*h*(2 - 2^(1-k)) + r*rest/2^k = 2rh - (2rh - r*rest)/2^k, rounding
+        // the subtracted part up so the result never exceeds the exact one.
+        let limit = (reward_rate as u128)
+            .checked_mul(half_life_seconds as u128)
+            .and_then(|value| value.checked_mul(2))
+            .ok_or_else(|| error!(Errors::CalculationFail))?;
+        let pending = limit - (reward_rate as u128) * (rest as u128);
+        let pending = if halvings >= 128 {
+            1
+        } else {
+            (pending >> halvings) + u128::from(pending & ((1 << halvings) - 1) != 0)
+        };
+
+        return Ok(limit - pending);
+    }
+
+    // r*h/ln(2) * (1 - 2^(-elapsed/h))
+    let remaining = if halvings >= 128 {
+        0
+    } else {
+        exp2_neg(rest, half_life_seconds) >> halvings
+    };
+    mul_shr_64((1 << 64) - remaining, INV_LN2_Q64)
+        .and_then(|value| value.checked_mul(half_life_seconds as u128))
+        .and_then(|value| mul_shr_64(reward_rate as u128, value))
+        .ok_or_else(|| error!(Errors::CalculationFail))
+}
+
+/// Size of `attestation_message`.
+pub const ATTESTATION_MESSAGE_LEN: usize = 32 + 32 + 8;
+
+/// Message an attestor signs to let `user` stake in `staking_pool` until the
+/// unix timestamp `expiry`.
+pub fn attestation_message(
+    staking_pool: &Pubkey,
+    user: &Pubkey,
+    expiry: u64,
+) -> [u8; ATTESTATION_MESSAGE_LEN] {
+    let mut message = [0u8; ATTESTATION_MESSAGE_LEN];
+    message[..32].copy_from_slice(staking_pool.as_ref());
+    message[32..64].copy_from_slice(user.as_ref());
+    message[64..].copy_from_slice(&expiry.to_le_bytes());
+    message
+}
+
+/// Checks the instruction right before the current one is an ed25519
+/// program verification, by `attestor`, of the attestation for
+/// `staking_pool` and `user`, and that it has not expired.
+pub fn check_attestation(
+    instructions_sysvar: &AccountInfo,
+    attestor: &Pubkey,
+    staking_pool: &Pubkey,
+    user: &Pubkey,
+) -> Result<()> {
+    let current_index = load_current_index_checked(instructions_sysvar)?;
+    require_gt!(current_index, 0, Errors::InvalidAttestation);
+    let instruction = load_instruction_at_checked(current_index as usize - 1, instructions_sysvar)?;
+    require_keys_eq!(
+        instruction.program_id,
+        ed25519_program::ID,
+        Errors::InvalidAttestation
+    );
+
+    let expiry = attestation_expiry(&instruction.data, attestor, staking_pool, user)?;
+    require_gt!(
+        expiry,
+        Clock::get()?.unix_timestamp as u64,
+        Errors::AttestationExpired
+    );
+
+    Ok(())
+}
+
+/// Expiry of the attestation verified by the ed25519 program instruction
+/// `data`, which must hold a single signature by `attestor` of the
+/// attestation for `staking_pool` and `user`.
+pub fn attestation_expiry(
+    data: &[u8],
+    attestor: &Pubkey,
+    staking_pool: &Pubkey,
+    user: &Pubkey,
+) -> Result<u64> {
+    // Layout of a single signature with all data in the instruction itself:
+    // count and padding, then `Ed25519SignatureOffsets`.
+    require!(data.len() >= 16 && data[0] == 1, Errors::InvalidAttestation);
+    let read_u16 = |offset: usize| u16::from_le_bytes([data[offset], data[offset + 1]]) as usize;
+    for instruction_index_offset in [4, 8, 14] {
+        require_eq!(
+            read_u16(instruction_index_offset),
+            u16::MAX as usize,
+            Errors::InvalidAttestation
+        );
+    }
+    let public_key_offset = read_u16(6);
+    let message_offset = read_u16(10);
+    let message_size = read_u16(12);
+    require_eq!(
+        message_size,
+        ATTESTATION_MESSAGE_LEN,
+        Errors::InvalidAttestation
+    );
+
+    let public_key = data
+        .get(public_key_offset..public_key_offset + 32)
+        .ok_or_else(|| error!(Errors::InvalidAttestation))?;
+    require!(public_key == attestor.as_ref(), Errors::InvalidAttestation);
+
+    let message = data
+        .get(message_offset..message_offset + message_size)
+        .ok_or_else(|| error!(Errors::InvalidAttestation))?;
+    require!(
+        message[..64] == attestation_message(staking_pool, user, 0)[..64],
+        Errors::InvalidAttestation
+    );
+
+    Ok(u64::from_le_bytes(message[64..].try_into().unwrap()))
+}
+
+/// Same as `token_interface::transfer_checked`, but also resolves the
+/// transfer hook accounts of Token-2022 mints from `remaining_accounts`.
+pub fn transfer_checked<'info>(
+    ctx: CpiContext<'_, '_, '_, 'info, TransferChecked<'info>>,
+    amount: u64,
+    decimals: u8,
+) -> Result<()> {
+    spl_token_2022::onchain::invoke_transfer_checked(
+        ctx.program.key,
+        ctx.accounts.from,
+        ctx.accounts.mint,
+        ctx.accounts.to,
+        ctx.accounts.authority,
+        &ctx.remaining_accounts,
+        amount,
+        decimals,
+        ctx.signer_seeds,
+    )
+    .map_err(Into::into)
+}
+
+/// Same as `transfer_checked`, but returns the amount that actually arrived
+/// in `destination`, which Token-2022 transfer fees withhold part of.
+pub fn transfer_checked_received<'info>(
+    ctx: CpiContext<'_, '_, '_, 'info, TransferChecked<'info>>,
+    destination: &mut InterfaceAccount<'info, TokenAccount>,
+    amount: u64,
+    decimals: u8,
+) -> Result<u64> {
+    let balance_before = destination.amount;
+
+    transfer_checked(ctx, amount, decimals)?;
+
+    destination.reload()?;
+    destination
+        .amount
+        .checked_sub(balance_before)
+        .ok_or_else(|| error!(Errors::CalculationFail))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `value` in Q64.64, to the precision of an `f64`.
+    fn q64(value: f64) -> u128 {
+        (value * 2f64.powi(64)) as u128
+    }
+
+    #[test]
+    fn exp2_neg_table_matches_reference() {
+        // f64 keeps 53 bits, so expect agreement to about 2^-50.
+        let tolerance = 1 << 14;
+        for (i, factor) in EXP2_NEG_POW2_Q64.iter().enumerate() {
+            let reference = q64(2f64.powf(-2f64.powi(-(i as i32 + 1))));
+            assert!(factor.abs_diff(reference) <= tolerance, "entry {}", i);
+        }
+        assert!(INV_LN2_Q64.abs_diff(q64(1.0 / 2f64.ln())) <= tolerance);
+
+        // Each entry squared is the previous one, exactly up to rounding.
+        let mut previous = 1 << 63;
+        for factor in EXP2_NEG_POW2_Q64 {
+            assert!(((factor * factor) >> 64).abs_diff(previous) <= 2);
+            previous = factor;
+        }
+    }
+
+    #[test]
+    fn exp2_neg_matches_reference() {
+        assert_eq!(exp2_neg(0, 7), 1 << 64);
+        // Only 40 bits of the fraction are used, so expect about 2^-40.
+        for (numerator, denominator) in [(1, 2), (1, 3), (2, 3), (99, 100), (12_345, 86_400)] {
+            let reference = q64(2f64.powf(-(numerator as f64) / (denominator as f64)));
+            assert!(
+                exp2_neg(numerator, denominator).abs_diff(reference) <= 1 << 24,
+                "{}/{}",
+                numerator,
+                denominator
+            );
+        }
+    }
+
+    #[test]
+    fn decayed_emission_at_zero_elapsed() {
+        assert_eq!(decayed_emission(1_000, 100, false, 0).unwrap(), 0);
+        assert_eq!(decayed_emission(1_000, 100, true, 0).unwrap(), 0);
+    }
+
+    #[test]
+    fn decayed_emission_stepwise() {
+        // 1000 a second for the first 100 seconds, 500 for the next 100, ...
+        assert_eq!(decayed_emission(1_000, 100, false, 50).unwrap(), 50_000);
+        assert_eq!(decayed_emission(1_000, 100, false, 100).unwrap(), 100_000);
+        assert_eq!(decayed_emission(1_000, 100, false, 150).unwrap(), 125_000);
+        assert_eq!(decayed_emission(1_000, 100, false, 200).unwrap(), 150_000);
+        assert_eq!(decayed_emission(1_000, 100, false, 300).unwrap(), 175_000);
+    }
+
+    #[test]
+    fn decayed_emission_continuous() {
+        // r*h/ln(2) * (1 - 2^(-t/h)): 72134.75 after one half-life, 108202.1
+        // after two.
+        assert_eq!(decayed_emission(1_000, 100, true, 100).unwrap(), 72_134);
+        assert_eq!(decayed_emission(1_000, 100, true, 200).unwrap(), 108_202);
+
+        // Never more than the undecayed rate would pay.
+        for elapsed in [1, 10, 99, 100, 101, 1_000] {
+            assert!(
+                decayed_emission(1_000, 100, true, elapsed).unwrap() <= 1_000 * elapsed as u128
+            );
+        }
+    }
+
+    #[test]
+    fn decayed_emission_converges_after_many_half_lives() {
+        // Stepwise approaches 2*r*h from below, continuous reaches r*h/ln(2)
+        // once the remaining part underflows.
+        let mut previous = (0, 0);
+        for halvings in [10, 63, 64, 65, 127, 128, 1_000] {
+            let stepwise = decayed_emission(1_000, 100, false, halvings * 100).unwrap();
+            let continuous = decayed_emission(1_000, 100, true, halvings * 100).unwrap();
+            assert!(stepwise >= previous.0 && stepwise < 200_000);
+            assert!(continuous >= previous.1 && continuous <= 144_269);
+            previous = (stepwise, continuous);
+        }
+        assert_eq!(previous, (199_999, 144_269));
+    }
+
+    #[test]
+    fn decayed_emission_at_large_elapsed() {
+        let half_life_seconds = u32::MAX as u64;
+        let limit = 2 * (u64::MAX as u128) * (half_life_seconds as u128);
+
+        assert_eq!(
+            decayed_emission(u64::MAX, half_life_seconds, false, u64::MAX).unwrap(),
+            limit - 1
+        );
+        let continuous = decayed_emission(u64::MAX, half_life_seconds, true, u64::MAX).unwrap();
+        assert!(continuous.abs_diff((limit as f64 / 2.0 / 2f64.ln()) as u128) <= limit >> 48);
+
+        // The stepwise limit itself overflows for the largest rates.
+        assert_eq!(
+            decayed_emission(u64::MAX, u64::MAX, false, 1).unwrap_err(),
+            error!(Errors::CalculationFail)
+        );
+    }
+
+    /// Ed25519 program data for one signature of `message` by `public_key`,
+    /// laid out as `new_ed25519_instruction` does.
+    fn ed25519_data(public_key: &Pubkey, message: &[u8]) -> Vec<u8> {
+        let (public_key_offset, signature_offset, message_offset) = (16u16, 48u16, 112u16);
+        let mut data = vec![1, 0];
+        for value in [
+            signature_offset,
+            u16::MAX,
+            public_key_offset,
+            u16::MAX,
+            message_offset,
+            message.len() as u16,
+            u16::MAX,
+        ] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        data.extend_from_slice(public_key.as_ref());
+        data.extend_from_slice(&[0u8; 64]);
+        data.extend_from_slice(message);
+        data
+    }
+
+    #[test]
+    fn attestation_expiry_of_valid_data() {
+        let (attestor, staking_pool, user) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let data = ed25519_data(
+            &attestor,
+            &attestation_message(&staking_pool, &user, 1_700_000_000),
+        );
+
+        assert_eq!(
+            attestation_expiry(&data, &attestor, &staking_pool, &user).unwrap(),
+            1_700_000_000
+        );
+    }
+
+    #[test]
+    fn attestation_expiry_rejects_other_signer_or_message() {
+        let (attestor, staking_pool, user) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let invalid = error!(Errors::InvalidAttestation);
+
+        let data = ed25519_data(
+            &Pubkey::new_unique(),
+            &attestation_message(&staking_pool, &user, 1),
+        );
+        assert_eq!(
+            attestation_expiry(&data, &attestor, &staking_pool, &user).unwrap_err(),
+            invalid
+        );
+
+        let data = ed25519_data(
+            &attestor,
+            &attestation_message(&staking_pool, &Pubkey::new_unique(), 1),
+        );
+        assert_eq!(
+            attestation_expiry(&data, &attestor, &staking_pool, &user).unwrap_err(),
+            invalid
+        );
+
+        let data = ed25519_data(
+            &attestor,
+            &attestation_message(&Pubkey::new_unique(), &user, 1),
+        );
+        assert_eq!(
+            attestation_expiry(&data, &attestor, &staking_pool, &user).unwrap_err(),
+            invalid
+        );
+
+        let message = attestation_message(&staking_pool, &user, 1);
+        let data = ed25519_data(&attestor, &message[..64]);
+        assert_eq!(
+            attestation_expiry(&data, &attestor, &staking_pool, &user).unwrap_err(),
+            invalid
+        );
+    }
+
+    #[test]
+    fn attestation_expiry_rejects_bad_offsets() {
+        let (attestor, staking_pool, user) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let valid = ed25519_data(&attestor, &attestation_message(&staking_pool, &user, 1));
+        let invalid = error!(Errors::InvalidAttestation);
+        let with = |offset: usize, bytes: &[u8]| {
+            let mut data = valid.clone();
+            data[offset..offset + bytes.len()].copy_from_slice(bytes);
+            data
+        };
+
+        for data in [
+            // Truncated header.
+            valid[..15].to_vec(),
+            // Two signatures.
+            with(0, &[2]),
+            // Signature, public key or message in another instruction.
+            with(4, &0u16.to_le_bytes()),
+            with(8, &0u16.to_le_bytes()),
+            with(14, &0u16.to_le_bytes()),
+            // Public key or message past the end of the data.
+            with(6, &(valid.len() as u16 - 16).to_le_bytes()),
+            with(10, &(valid.len() as u16 - 8).to_le_bytes()),
+            // Message pointing at the public key.
+            with(10, &16u16.to_le_bytes()),
+        ] {
+            assert_eq!(
+                attestation_expiry(&data, &attestor, &staking_pool, &user).unwrap_err(),
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn mul_div_matches_u128_math() {
+        for (a, b, divisor) in [
+            (0, 0, 1),
+            (7, 3, 2),
+            (u64::MAX as u128, u64::MAX, u64::MAX),
+            (
+                REWARD_PER_SHARE_BASE * 12_345,
+                678,
+                REWARD_PER_SHARE_BASE as u64,
+            ),
+        ] {
+            assert_eq!(
+                mul_div(a, b, divisor),
+                Some(a * (b as u128) / (divisor as u128))
+            );
+        }
+    }
+
+    #[test]
+    fn mul_div_past_u128_product() {
+        // The product needs 192 bits, the quotient fits again.
+        assert_eq!(mul_div(u128::MAX, u64::MAX, u64::MAX), Some(u128::MAX));
+        assert_eq!(mul_div(u128::MAX / 3, 6, 2), Some(u128::MAX / 3 * 3));
+        assert_eq!(mul_div(u128::MAX, 2, 1), None);
+        assert_eq!(mul_div(1, 1, 0), None);
+    }
+}
@@ -3,9 +3,7 @@ pub use crate::StakingPool;
 use crate::{helper, RewardAlgorithm};
 use anchor_lang::prelude::*;
 use anchor_spl::associated_token::AssociatedToken;
-use anchor_spl::token_interface::{
-    transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked,
-};
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked};
 
 #[derive(Accounts)]
 #[instruction(params: InitializeStakingPoolParams)]
@@ -19,7 +17,7 @@ pub struct InitializeStakingPool<'info> {
 
     #[account(
         init,
-        space = 8 + std::mem::size_of::<StakingPool>(),
+        space = StakingPool::SPACE,
         payer = rent_payer,
         rent_exempt = enforce,
         seeds = [
@@ -63,6 +61,7 @@ pub struct InitializeStakingPoolParams {
     pub unbonding_seconds: u64,
     pub reward_algorithm: RewardAlgorithm,
     pub index: u8,
+    pub extension_policy: u8,
 }
 
 impl<'info> InitializeStakingPool<'info> {
@@ -70,10 +69,16 @@ impl<'info> InitializeStakingPool<'info> {
         &mut self,
         params: InitializeStakingPoolParams,
         pool_seed_bump: u8,
+        remaining_accounts: &[AccountInfo<'info>],
     ) -> Result<()> {
         require_gt!(params.reward_rate, 0, Errors::ParamsNotMatch);
         require_gt!(params.unbonding_seconds, 0, Errors::ParamsNotMatch);
+        params.reward_algorithm.check()?;
 
+        helper::check_mint_extensions(&self.token_mint, params.extension_policy)?;
+        helper::check_transfer_hook_accounts(params.extension_policy, remaining_accounts)?;
+
+        let mut total_reward = 0;
         if params.total_reward > 0 {
             let transfer_to_pool_cpi_context = CpiContext::new(
                 self.token_program.to_account_info(),
@@ -83,9 +88,11 @@ impl<'info> InitializeStakingPool<'info> {
                     to: self.pool_token_account.to_account_info(),
                     authority: self.admin.to_account_info(),
                 },
-            );
-            transfer_checked(
+            )
+            .with_remaining_accounts(remaining_accounts.to_vec());
+            total_reward = helper::transfer_checked_received(
                 transfer_to_pool_cpi_context,
+                &mut self.pool_token_account,
                 params.total_reward,
                 self.token_mint.decimals,
             )?;
@@ -100,14 +107,35 @@ impl<'info> InitializeStakingPool<'info> {
             token_mint: self.token_mint.key(),
             min_stake_amount: helper::DEFAULT_MIN_STAKE_AMOUNT,
             total_stake: 0,
-            total_reward: params.total_reward,
-            undistributed_reward: params.total_reward,
+            total_reward,
+            undistributed_reward: total_reward,
             reward_rate: params.reward_rate,
             reward_algorithm: params.reward_algorithm,
             last_reward_timestamp: 0,
             reward_per_share: 0,
             unbonding_seconds: params.unbonding_seconds,
-            _reserved: [0u8; 256],
+            extension_policy: params.extension_policy,
+            slasher: Pubkey::default(),
+            stake_scale: 0,
+            unbonding_scale: 0,
+            total_unbonding: 0,
+            total_owed_rewards: 0,
+            version: helper::POOL_VERSION,
+            reward_remainder: 0,
+            reward_per_share_remainder: 0,
+            min_stake_duration: 0,
+            max_loyalty_multiplier: 0,
+            loyalty_ramp_seconds: 0,
+            total_weight: 0,
+            linear_unbonding: false,
+            allowlist_root: [0u8; 32],
+            attestor: Pubkey::default(),
+            reward_schedule: vec![],
+            epoch_length: 0,
+            pending_weight: 0,
+            epoch_history: vec![],
+            owed_rewards_tracked: true,
+            _reserved: [0u8; 4],
         });
 
         Ok(())
@@ -1,31 +1,45 @@
 use anchor_lang::{prelude::*, Bumps};
 
 pub mod admin;
+pub mod audit_pool;
 pub mod errors;
 pub mod helper;
 pub mod initialize_staking_pool;
+pub mod settle_epochs;
+pub mod slasher_slash;
 pub mod staker_claim;
+pub mod staker_migrate_stake;
+pub mod staker_relocate_stake;
 pub mod staker_stake;
+pub mod staker_stake_for;
 pub mod staker_unstake;
+pub mod staker_update_voter_weight;
 pub mod staker_withdraw;
 pub mod states;
+pub mod view_balance_at;
 
 pub use crate::admin::*;
+pub use crate::audit_pool::*;
 pub use crate::errors::Errors;
 pub use crate::helper::*;
 pub use crate::initialize_staking_pool::*;
+pub use crate::settle_epochs::*;
+pub use crate::slasher_slash::*;
 pub use crate::staker_claim::*;
+pub use crate::staker_migrate_stake::*;
+pub use crate::staker_relocate_stake::*;
 pub use crate::staker_stake::*;
+pub use crate::staker_stake_for::*;
 pub use crate::staker_unstake::*;
+pub use crate::staker_update_voter_weight::*;
 pub use crate::staker_withdraw::*;
 pub use crate::states::*;
+pub use crate::view_balance_at::*;
 
 declare_id!("ASVEfWrLMRd9YeAWJviTF1CMAd2anTM9o83Y5DNqnmyp");
 
 fn check_context<T: Bumps>(ctx: &Context<T>) -> Result<()> {
-    if !check_id(ctx.program_id) {
-        return err!(Errors::ProgramIdNotMatch);
-    }
+    check_program_id(ctx)?;
 
     if !ctx.remaining_accounts.is_empty() {
         return err!(Errors::RemainingAccountsNotMatch);
@@ -34,6 +48,16 @@ fn check_context<T: Bumps>(ctx: &Context<T>) -> Result<()> {
     Ok(())
 }
 
+/// For instructions moving tokens, the remaining accounts are forwarded to
+/// the mint's transfer hook and checked against the pool policy in `process`.
+fn check_program_id<T: Bumps>(ctx: &Context<T>) -> Result<()> {
+    if !check_id(ctx.program_id) {
+        return err!(Errors::ProgramIdNotMatch);
+    }
+
+    Ok(())
+}
+
 #[program]
 pub mod staking_program {
 
@@ -41,13 +65,14 @@ pub mod staking_program {
 
     // initialize account
 
-    pub fn initialize_staking_pool(
-        ctx: Context<InitializeStakingPool>,
+    pub fn initialize_staking_pool<'info>(
+        ctx: Context<'_, '_, '_, 'info, InitializeStakingPool<'info>>,
         params: InitializeStakingPoolParams,
     ) -> Result<()> {
-        check_context(&ctx)?;
+        check_program_id(&ctx)?;
 
-        ctx.accounts.process(params, ctx.bumps.staking_pool)?;
+        ctx.accounts
+            .process(params, ctx.bumps.staking_pool, ctx.remaining_accounts)?;
 
         Ok(())
     }
@@ -78,24 +103,160 @@ pub mod staking_program {
         Ok(())
     }
 
-    pub fn add_rewards(ctx: Context<AddRewards>, amount: u64) -> Result<()> {
+    pub fn schedule_reward_change(
+        ctx: Context<ScheduleRewardChange>,
+        change: ScheduledRewardChange,
+    ) -> Result<()> {
         check_context(&ctx)?;
 
-        ctx.accounts.process(amount)?;
+        ctx.accounts.process(change)?;
 
         Ok(())
     }
 
-    // staker
+    pub fn cancel_reward_change(
+        ctx: Context<CancelRewardChange>,
+        effective_timestamp: u64,
+    ) -> Result<()> {
+        check_context(&ctx)?;
+
+        ctx.accounts.process(effective_timestamp)?;
+
+        Ok(())
+    }
+
+    /// Switches the pool to epoch rewards for good, see
+    /// `StakingPool::epoch_length`. `reward_rate` is paid per epoch.
+    pub fn enable_epoch_mode(
+        ctx: Context<EnableEpochMode>,
+        epoch_length: u64,
+        reward_rate: u64,
+    ) -> Result<()> {
+        check_context(&ctx)?;
+
+        ctx.accounts.process(epoch_length, reward_rate)?;
+
+        Ok(())
+    }
+
+    /// Lets `audit_pool` report on pools created before owed rewards were
+    /// tracked, see `BackfillOwedRewards::process`.
+    pub fn backfill_owed_rewards(
+        ctx: Context<BackfillOwedRewards>,
+        owed_rewards: u64,
+        reward_per_share: u128,
+    ) -> Result<()> {
+        check_context(&ctx)?;
+
+        ctx.accounts.process(owed_rewards, reward_per_share)?;
 
-    pub fn stake(ctx: Context<Stake>, stake_amount: u64) -> Result<()> {
+        Ok(())
+    }
+
+    pub fn add_rewards<'info>(
+        ctx: Context<'_, '_, '_, 'info, AddRewards<'info>>,
+        amount: u64,
+    ) -> Result<()> {
+        check_program_id(&ctx)?;
+
+        ctx.accounts.process(amount, ctx.remaining_accounts)?;
+
+        Ok(())
+    }
+
+    pub fn grant_vesting_stake<'info>(
+        ctx: Context<'_, '_, '_, 'info, GrantVestingStake<'info>>,
+        beneficiary: Pubkey,
+        params: GrantVestingStakeParams,
+    ) -> Result<()> {
+        check_program_id(&ctx)?;
+
+        ctx.accounts
+            .process(beneficiary, params, ctx.remaining_accounts)?;
+
+        Ok(())
+    }
+
+    pub fn approve_migration_link(
+        ctx: Context<ApproveMigrationLink>,
+        approved: bool,
+    ) -> Result<()> {
+        check_context(&ctx)?;
+
+        ctx.accounts.process(approved)?;
+
+        Ok(())
+    }
+
+    pub fn set_blocklist(
+        ctx: Context<SetBlocklist>,
+        user: Pubkey,
+        reason: u16,
+        frozen: bool,
+    ) -> Result<()> {
         check_context(&ctx)?;
 
-        ctx.accounts.process(stake_amount)?;
+        ctx.accounts.process(user, reason, frozen)?;
+
+        Ok(())
+    }
+
+    pub fn clear_blocklist(ctx: Context<ClearBlocklist>) -> Result<()> {
+        check_context(&ctx)?;
+
+        ctx.accounts.process()?;
+
+        Ok(())
+    }
+
+    // slasher
+
+    pub fn slash<'info>(
+        ctx: Context<'_, '_, '_, 'info, Slash<'info>>,
+        amount: u64,
+        include_unbonding: bool,
+    ) -> Result<()> {
+        check_program_id(&ctx)?;
+
+        ctx.accounts
+            .process(amount, include_unbonding, ctx.remaining_accounts)?;
+
+        Ok(())
+    }
+
+    // staker
+
+    /// `proof` is only needed when the pool has an allowlist, see
+    /// `StakingPool::check_allowlist`. Pools with an attestor also need an
+    /// ed25519 verification right before, see `helper::check_attestation`.
+    pub fn stake<'info>(
+        ctx: Context<'_, '_, '_, 'info, Stake<'info>>,
+        stake_amount: u64,
+        proof: Vec<[u8; 32]>,
+    ) -> Result<()> {
+        check_program_id(&ctx)?;
+
+        ctx.accounts
+            .process(stake_amount, proof, ctx.remaining_accounts)?;
 
         Ok(())
     }
 
+    pub fn stake_for<'info>(
+        ctx: Context<'_, '_, '_, 'info, StakeFor<'info>>,
+        beneficiary: Pubkey,
+        stake_amount: u64,
+        proof: Vec<[u8; 32]>,
+    ) -> Result<()> {
+        check_program_id(&ctx)?;
+
+        ctx.accounts
+            .process(beneficiary, stake_amount, proof, ctx.remaining_accounts)?;
+
+        Ok(())
+    }
+
+    /// `unstake_amount` is in stake units, see `StakeAccount::amount`.
     pub fn unstake(ctx: Context<Unstake>, unstake_amount: u64) -> Result<()> {
         check_context(&ctx)?;
 
@@ -104,7 +265,66 @@ pub mod staking_program {
         Ok(())
     }
 
-    pub fn withdraw(ctx: Context<Withdraw>) -> Result<()> {
+    pub fn withdraw<'info>(ctx: Context<'_, '_, '_, 'info, Withdraw<'info>>) -> Result<()> {
+        check_program_id(&ctx)?;
+
+        ctx.accounts.process(ctx.remaining_accounts)?;
+
+        Ok(())
+    }
+
+    pub fn claim<'info>(
+        ctx: Context<'_, '_, '_, 'info, Claim<'info>>,
+        restake: bool,
+    ) -> Result<()> {
+        check_program_id(&ctx)?;
+
+        ctx.accounts.process(restake, ctx.remaining_accounts)?;
+
+        Ok(())
+    }
+
+    /// `migrate_amount` is in stake units of the source pool. `proof` is only
+    /// needed when the destination pool has an allowlist.
+    pub fn migrate_stake<'info>(
+        ctx: Context<'_, '_, '_, 'info, MigrateStake<'info>>,
+        migrate_amount: u64,
+        proof: Vec<[u8; 32]>,
+    ) -> Result<()> {
+        check_program_id(&ctx)?;
+
+        ctx.accounts
+            .process(migrate_amount, proof, ctx.remaining_accounts)?;
+
+        Ok(())
+    }
+
+    pub fn relocate_stake(ctx: Context<RelocateStake>) -> Result<()> {
+        check_context(&ctx)?;
+
+        ctx.accounts.process()?;
+
+        Ok(())
+    }
+
+    // governance
+
+    pub fn update_voter_weight_record<'info>(
+        ctx: Context<'_, '_, '_, 'info, UpdateVoterWeightRecord<'info>>,
+        include_unbonding: bool,
+        include_loyalty: bool,
+    ) -> Result<()> {
+        check_program_id(&ctx)?;
+
+        ctx.accounts
+            .process(include_unbonding, include_loyalty, ctx.remaining_accounts)?;
+
+        Ok(())
+    }
+
+    // crank
+
+    pub fn settle_epochs(ctx: Context<SettleEpochs>) -> Result<()> {
         check_context(&ctx)?;
 
         ctx.accounts.process()?;
@@ -112,11 +332,21 @@ pub mod staking_program {
         Ok(())
     }
 
-    pub fn claim(ctx: Context<Claim>, restake: bool) -> Result<()> {
+    // monitoring
+
+    pub fn audit_pool(ctx: Context<AuditPool>) -> Result<()> {
         check_context(&ctx)?;
 
-        ctx.accounts.process(restake)?;
+        ctx.accounts.process()?;
 
         Ok(())
     }
+
+    // view
+
+    pub fn get_balance_at(ctx: Context<GetBalanceAt>, slot: u64) -> Result<u64> {
+        check_context(&ctx)?;
+
+        ctx.accounts.process(slot)
+    }
 }
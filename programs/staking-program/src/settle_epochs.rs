@@ -0,0 +1,38 @@
+use crate::{Errors, StakingPool};
+use anchor_lang::prelude::*;
+
+#[derive(Accounts)]
+pub struct SettleEpochs<'info> {
+    #[account(mut)]
+    pub staking_pool: Box<Account<'info, StakingPool>>,
+}
+
+#[event]
+pub struct EventSettleEpochs {
+    pub staking_pool: Pubkey,
+    pub from_epoch: u64,
+    pub to_epoch: u64,
+    pub reward_per_share: u128,
+}
+
+impl<'info> SettleEpochs<'info> {
+    /// Settles the ended epochs of an epoch mode pool, so stakers see their
+    /// rewards without waiting for the next stake instruction.
+    pub fn process(&mut self) -> Result<()> {
+        require!(self.staking_pool.is_epoch_mode(), Errors::ParamsNotMatch);
+
+        let from_epoch = self.staking_pool.open_epoch();
+        self.staking_pool.update_pool()?;
+        let to_epoch = self.staking_pool.open_epoch();
+
+        if to_epoch > from_epoch {
+            emit!(EventSettleEpochs {
+                staking_pool: self.staking_pool.key(),
+                from_epoch,
+                to_epoch: to_epoch - 1,
+                reward_per_share: self.staking_pool.reward_per_share,
+            });
+        }
+        Ok(())
+    }
+}
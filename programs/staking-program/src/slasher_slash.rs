@@ -0,0 +1,145 @@
+use crate::{helper, Errors, StakingPool};
+use anchor_lang::prelude::*;
+use anchor_spl::associated_token::AssociatedToken;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked};
+
+#[derive(Accounts)]
+pub struct Slash<'info> {
+    pub slasher: Signer<'info>,
+
+    #[account(
+        mut,
+        has_one = slasher @ Errors::SlasherNotMatch
+    )]
+    pub staking_pool: Box<Account<'info, StakingPool>>,
+
+    #[account(
+        address = staking_pool.token_mint @Errors::TokenMintAccountNotMatch
+    )]
+    pub token_mint: Box<InterfaceAccount<'info, Mint>>,
+
+    #[account(
+        mut,
+        associated_token::mint = token_mint,
+        associated_token::authority = staking_pool,
+        associated_token::token_program = token_program,
+    )]
+    pub pool_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        token::mint = token_mint,
+        token::token_program = token_program,
+    )]
+    pub recipient: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
+
+#[event]
+pub struct EventSlash {
+    pub slasher: Pubkey,
+    pub recipient: Pubkey,
+    pub amount: u64,
+    pub stake_amount: u64,
+    pub unbonding_amount: u64,
+    pub staking_pool: Pubkey,
+}
+
+impl<'info> Slash<'info> {
+    /// Takes `amount` out of the pool, split between staked and, with
+    /// `include_unbonding`, unbonding tokens by their share of the total.
+    /// Positions are not touched: the pool scales shrink instead, so every
+    /// staker loses the same fraction. Stake units don't change, so neither
+    /// do the balance checkpoints.
+    pub fn process(
+        &mut self,
+        amount: u64,
+        include_unbonding: bool,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        require_keys_neq!(
+            self.staking_pool.slasher,
+            Pubkey::default(),
+            Errors::SlasherNotMatch
+        );
+        helper::check_transfer_hook_accounts(
+            self.staking_pool.extension_policy,
+            remaining_accounts,
+        )?;
+
+        // Rewards earned so far are not slashed.
+        self.staking_pool.update_pool()?;
+
+        let total_stake = self.staking_pool.total_stake_tokens()?;
+        let total_unbonding = if include_unbonding {
+            self.staking_pool.total_unbonding
+        } else {
+            0
+        };
+        let total = total_stake as u128 + total_unbonding as u128;
+
+        require_gt!(amount, 0, Errors::ParamsNotMatch);
+        // Leave something behind so the scales never reach zero.
+        require_gt!(total, amount as u128, Errors::SlashAmountTooHigh);
+
+        let stake_amount = ((amount as u128) * (total_stake as u128) / total) as u64;
+        let unbonding_amount = amount - stake_amount;
+
+        if stake_amount > 0 {
+            let stake_scale = self.staking_pool.effective_stake_scale()
+                * ((total_stake - stake_amount) as u128)
+                / (total_stake as u128);
+            require_gt!(stake_scale, 0, Errors::SlashAmountTooHigh);
+            self.staking_pool.stake_scale = stake_scale;
+        }
+        if unbonding_amount > 0 {
+            let unbonding_scale = self.staking_pool.effective_unbonding_scale()
+                * ((total_unbonding - unbonding_amount) as u128)
+                / (total_unbonding as u128);
+            require_gt!(unbonding_scale, 0, Errors::SlashAmountTooHigh);
+            self.staking_pool.unbonding_scale = unbonding_scale;
+            self.staking_pool.total_unbonding -= unbonding_amount;
+        }
+
+        require_gte!(
+            self.pool_token_account.amount,
+            amount,
+            Errors::PoolBalanceNotEnough
+        );
+
+        helper::transfer_checked(
+            CpiContext::new_with_signer(
+                self.token_program.to_account_info(),
+                TransferChecked {
+                    from: self.pool_token_account.to_account_info(),
+                    mint: self.token_mint.to_account_info(),
+                    to: self.recipient.to_account_info(),
+                    authority: self.staking_pool.to_account_info(),
+                },
+                &[&[
+                    helper::POOL_SEED,
+                    &self.staking_pool.token_mint.key().to_bytes(),
+                    &self.staking_pool.creator.key().to_bytes(),
+                    &[self.staking_pool.index],
+                    &[self.staking_pool.pool_seed_bump],
+                ]],
+            )
+            .with_remaining_accounts(remaining_accounts.to_vec()),
+            amount,
+            self.token_mint.decimals,
+        )?;
+
+        emit!(EventSlash {
+            slasher: self.slasher.key(),
+            recipient: self.recipient.key(),
+            amount,
+            stake_amount,
+            unbonding_amount,
+            staking_pool: self.staking_pool.key(),
+        });
+
+        Ok(())
+    }
+}
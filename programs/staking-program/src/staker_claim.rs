@@ -1,9 +1,7 @@
-use crate::{helper, Errors, StakeAccount, StakingPool};
+use crate::{helper, BalanceCheckpoints, Blocklist, Errors, StakeAccount, StakingPool};
 use anchor_lang::prelude::*;
 use anchor_spl::associated_token::AssociatedToken;
-use anchor_spl::token_interface::{
-    transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked,
-};
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked};
 
 #[derive(Accounts)]
 pub struct Claim<'info> {
@@ -22,6 +20,43 @@ pub struct Claim<'info> {
     )]
     pub stake_account: Box<Account<'info, StakeAccount>>,
 
+    #[account(
+        init_if_needed,
+        space = 8 + std::mem::size_of::<BalanceCheckpoints>(),
+        payer = rent_payer,
+        rent_exempt = enforce,
+        seeds = [
+            helper::CHECKPOINTS_SEED,
+            &staking_pool.key().to_bytes(),
+        ],
+        bump,
+    )]
+    pub pool_checkpoints: Box<Account<'info, BalanceCheckpoints>>,
+
+    #[account(
+        init_if_needed,
+        space = 8 + std::mem::size_of::<BalanceCheckpoints>(),
+        payer = rent_payer,
+        rent_exempt = enforce,
+        seeds = [
+            helper::CHECKPOINTS_SEED,
+            &stake_account.key().to_bytes(),
+        ],
+        bump,
+    )]
+    pub stake_checkpoints: Box<Account<'info, BalanceCheckpoints>>,
+
+    /// CHECK: blocklist entry of `user`, which must not exist.
+    #[account(
+        seeds = [
+            helper::BLOCKLIST_SEED,
+            &staking_pool.key().to_bytes(),
+            &user.key().to_bytes(),
+        ],
+        bump,
+    )]
+    pub blocklist: UncheckedAccount<'info>,
+
     #[account(
         address = staking_pool.token_mint @Errors::TokenMintAccountNotMatch
     )]
@@ -57,20 +92,53 @@ pub struct EventClaim {
 }
 
 impl<'info> Claim<'info> {
-    pub fn process(&mut self, restake: bool) -> Result<()> {
+    pub fn process(
+        &mut self,
+        restake: bool,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        helper::check_transfer_hook_accounts(
+            self.staking_pool.extension_policy,
+            remaining_accounts,
+        )?;
+        Blocklist::check_not_blocked(&self.blocklist)?;
+
+        let current_time = Clock::get()?.unix_timestamp as u64;
         self.staking_pool.update_pool()?;
 
-        self.stake_account
-            .update_reward(self.staking_pool.reward_per_share)?;
+        self.stake_account.update_reward(&self.staking_pool)?;
 
-        let claim_amount = self.stake_account.reward;
+        let claim_amount = u64::try_from(self.stake_account.reward)
+            .map_err(|_| error!(Errors::CalculationFail))?;
 
         if claim_amount > 0 {
             self.stake_account.reward = 0;
 
+            self.staking_pool
+                .release_owed_rewards(claim_amount, self.pool_token_account.amount)?;
+
             if restake {
-                self.stake_account.amount += claim_amount;
-                self.staking_pool.total_stake += claim_amount;
+                let previous_total_stake = self.staking_pool.total_stake;
+                let previous_amount = self.stake_account.amount;
+
+                let restake_amount = self.staking_pool.tokens_to_stake(claim_amount)?;
+                self.stake_account
+                    .add_amount(restake_amount, current_time)?;
+                self.staking_pool.total_stake += restake_amount;
+
+                let slot = Clock::get()?.slot;
+                self.pool_checkpoints.record(
+                    self.staking_pool.key(),
+                    slot,
+                    previous_total_stake,
+                    self.staking_pool.total_stake,
+                );
+                self.stake_checkpoints.record(
+                    self.stake_account.key(),
+                    slot,
+                    previous_amount,
+                    self.stake_account.amount,
+                );
             } else {
                 require_gte!(
                     self.pool_token_account.amount,
@@ -78,7 +146,7 @@ impl<'info> Claim<'info> {
                     Errors::PoolBalanceNotEnough
                 );
 
-                transfer_checked(
+                helper::transfer_checked(
                     CpiContext::new_with_signer(
                         self.token_program.to_account_info(),
                         TransferChecked {
@@ -94,7 +162,8 @@ impl<'info> Claim<'info> {
                             &[self.staking_pool.index],
                             &[self.staking_pool.pool_seed_bump],
                         ]],
-                    ),
+                    )
+                    .with_remaining_accounts(remaining_accounts.to_vec()),
                     claim_amount,
                     self.token_mint.decimals,
                 )?;
@@ -102,7 +171,7 @@ impl<'info> Claim<'info> {
         }
 
         self.stake_account
-            .update_reward_debt(self.staking_pool.reward_per_share)?;
+            .update_reward_debt(&mut self.staking_pool, current_time)?;
 
         emit!(EventClaim {
             user: self.user.key(),
@@ -0,0 +1,308 @@
+use crate::{
+    helper, BalanceCheckpoints, Blocklist, Errors, MigrationLink, StakeAccount, StakingPool,
+};
+use anchor_lang::prelude::*;
+use anchor_spl::associated_token::AssociatedToken;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked};
+
+#[derive(Accounts)]
+pub struct MigrateStake<'info> {
+    pub user: Signer<'info>,
+
+    #[account(mut)]
+    pub rent_payer: Signer<'info>,
+
+    #[account(mut)]
+    pub from_pool: Box<Account<'info, StakingPool>>,
+
+    #[account(mut)]
+    pub to_pool: Box<Account<'info, StakingPool>>,
+
+    #[account(
+        seeds = [
+            helper::MIGRATION_LINK_SEED,
+            &from_pool.key().to_bytes(),
+            &to_pool.key().to_bytes(),
+        ],
+        bump,
+        constraint = migration_link.is_approved() @Errors::MigrationNotApproved,
+    )]
+    pub migration_link: Box<Account<'info, MigrationLink>>,
+
+    #[account(
+        address = from_pool.token_mint @Errors::TokenMintAccountNotMatch,
+        constraint = to_pool.token_mint == token_mint.key() @Errors::TokenMintAccountNotMatch,
+    )]
+    pub token_mint: Box<InterfaceAccount<'info, Mint>>,
+
+    #[account(
+        mut,
+        associated_token::mint = token_mint,
+        associated_token::authority = from_pool,
+        associated_token::token_program = token_program,
+    )]
+    pub from_pool_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        associated_token::mint = token_mint,
+        associated_token::authority = to_pool,
+        associated_token::token_program = token_program,
+    )]
+    pub to_pool_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        constraint = from_stake_account.staking_pool == from_pool.key() @Errors::InvalidStakeAccount,
+        has_one = user @Errors::InvalidStakeAccount,
+    )]
+    pub from_stake_account: Box<Account<'info, StakeAccount>>,
+
+    #[account(
+        init_if_needed,
+        space = StakeAccount::SPACE,
+        payer = rent_payer,
+        rent_exempt = enforce,
+        seeds = [
+            helper::STAKE_ACCOUNT_SEED,
+            &to_pool.key().to_bytes(),
+            &user.key().to_bytes(),
+        ],
+        bump,
+    )]
+    pub to_stake_account: Box<Account<'info, StakeAccount>>,
+
+    #[account(
+        init_if_needed,
+        space = 8 + std::mem::size_of::<BalanceCheckpoints>(),
+        payer = rent_payer,
+        rent_exempt = enforce,
+        seeds = [
+            helper::CHECKPOINTS_SEED,
+            &from_pool.key().to_bytes(),
+        ],
+        bump,
+    )]
+    pub from_pool_checkpoints: Box<Account<'info, BalanceCheckpoints>>,
+
+    #[account(
+        init_if_needed,
+        space = 8 + std::mem::size_of::<BalanceCheckpoints>(),
+        payer = rent_payer,
+        rent_exempt = enforce,
+        seeds = [
+            helper::CHECKPOINTS_SEED,
+            &from_stake_account.key().to_bytes(),
+        ],
+        bump,
+    )]
+    pub from_stake_checkpoints: Box<Account<'info, BalanceCheckpoints>>,
+
+    #[account(
+        init_if_needed,
+        space = 8 + std::mem::size_of::<BalanceCheckpoints>(),
+        payer = rent_payer,
+        rent_exempt = enforce,
+        seeds = [
+            helper::CHECKPOINTS_SEED,
+            &to_pool.key().to_bytes(),
+        ],
+        bump,
+    )]
+    pub to_pool_checkpoints: Box<Account<'info, BalanceCheckpoints>>,
+
+    #[account(
+        init_if_needed,
+        space = 8 + std::mem::size_of::<BalanceCheckpoints>(),
+        payer = rent_payer,
+        rent_exempt = enforce,
+        seeds = [
+            helper::CHECKPOINTS_SEED,
+            &to_stake_account.key().to_bytes(),
+        ],
+        bump,
+    )]
+    pub to_stake_checkpoints: Box<Account<'info, BalanceCheckpoints>>,
+
+    /// CHECK: the instructions sysvar, for pools requiring attestations.
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    pub instructions_sysvar: UncheckedAccount<'info>,
+
+    /// CHECK: blocklist entry of `user` in `from_pool`, which must not freeze it.
+    #[account(
+        seeds = [
+            helper::BLOCKLIST_SEED,
+            &from_pool.key().to_bytes(),
+            &user.key().to_bytes(),
+        ],
+        bump,
+    )]
+    pub from_blocklist: UncheckedAccount<'info>,
+
+    /// CHECK: blocklist entry of `user` in `to_pool`, which must not exist.
+    #[account(
+        seeds = [
+            helper::BLOCKLIST_SEED,
+            &to_pool.key().to_bytes(),
+            &user.key().to_bytes(),
+        ],
+        bump,
+    )]
+    pub to_blocklist: UncheckedAccount<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[event]
+pub struct EventMigrateStake {
+    pub user: Pubkey,
+    pub from_pool: Pubkey,
+    pub to_pool: Pubkey,
+    /// Tokens taken out of `from_pool`.
+    pub migrate_amount: u64,
+    /// Tokens staked in `to_pool`, after any transfer fee.
+    pub stake_amount: u64,
+}
+
+impl<'info> MigrateStake<'info> {
+    /// Moves `migrate_amount` stake units from `from_pool` to `to_pool`
+    /// without unbonding. Rewards earned in `from_pool` stay claimable
+    /// there. `proof` is checked against the allowlist of `to_pool`.
+    pub fn process(
+        &mut self,
+        migrate_amount: u64,
+        proof: Vec<[u8; 32]>,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        helper::check_transfer_hook_accounts(self.from_pool.extension_policy, remaining_accounts)?;
+        helper::check_transfer_hook_accounts(self.to_pool.extension_policy, remaining_accounts)?;
+        Blocklist::check_not_frozen(&self.from_blocklist)?;
+        Blocklist::check_not_blocked(&self.to_blocklist)?;
+        self.to_pool.check_allowlist(&self.user.key(), &proof)?;
+        self.to_pool.check_attestation(
+            &self.to_pool.key(),
+            &self.user.key(),
+            &self.instructions_sysvar,
+        )?;
+
+        require_gt!(migrate_amount, 0, Errors::UnstakeAmountIsZero);
+        require_gte!(
+            self.from_stake_account.amount,
+            migrate_amount,
+            Errors::BalanceNotEnough
+        );
+
+        let current_time = Clock::get()?.unix_timestamp as u64;
+        require_gte!(
+            current_time,
+            self.from_stake_account
+                .stake_timestamp
+                .saturating_add(self.from_pool.min_stake_duration),
+            Errors::StakeDurationNotReached
+        );
+        require_gte!(
+            self.from_stake_account.amount - migrate_amount,
+            self.from_stake_account.locked_amount(current_time),
+            Errors::StakeAmountLocked
+        );
+
+        self.from_pool.update_pool()?;
+        self.to_pool.update_pool()?;
+
+        // Settle the source position.
+        self.from_stake_account.update_reward(&self.from_pool)?;
+
+        let previous_from_total_stake = self.from_pool.total_stake;
+        let previous_from_amount = self.from_stake_account.amount;
+
+        let migrate_tokens = self.from_pool.stake_to_tokens(migrate_amount)?;
+        self.from_stake_account
+            .remove_amount(migrate_amount, current_time)?;
+        self.from_stake_account
+            .update_reward_debt(&mut self.from_pool, current_time)?;
+        self.from_pool.total_stake -= migrate_amount;
+
+        require_gte!(
+            self.from_pool_token_account.amount,
+            migrate_tokens,
+            Errors::PoolBalanceNotEnough
+        );
+
+        let stake_amount = helper::transfer_checked_received(
+            CpiContext::new_with_signer(
+                self.token_program.to_account_info(),
+                TransferChecked {
+                    from: self.from_pool_token_account.to_account_info(),
+                    mint: self.token_mint.to_account_info(),
+                    to: self.to_pool_token_account.to_account_info(),
+                    authority: self.from_pool.to_account_info(),
+                },
+                &[&[
+                    helper::POOL_SEED,
+                    &self.from_pool.token_mint.key().to_bytes(),
+                    &self.from_pool.creator.key().to_bytes(),
+                    &[self.from_pool.index],
+                    &[self.from_pool.pool_seed_bump],
+                ]],
+            )
+            .with_remaining_accounts(remaining_accounts.to_vec()),
+            &mut self.to_pool_token_account,
+            migrate_tokens,
+            self.token_mint.decimals,
+        )?;
+
+        require_gte!(
+            stake_amount,
+            self.to_pool.min_stake_amount,
+            Errors::StakeAmountTooLow
+        );
+
+        let previous_to_total_stake = self.to_pool.total_stake;
+        let previous_to_amount = self.to_stake_account.amount;
+
+        let to_pool_key = self.to_pool.key();
+        self.to_stake_account.deposit(
+            &mut self.to_pool,
+            to_pool_key,
+            self.user.key(),
+            stake_amount,
+        )?;
+
+        let slot = Clock::get()?.slot;
+        self.from_pool_checkpoints.record(
+            self.from_pool.key(),
+            slot,
+            previous_from_total_stake,
+            self.from_pool.total_stake,
+        );
+        self.from_stake_checkpoints.record(
+            self.from_stake_account.key(),
+            slot,
+            previous_from_amount,
+            self.from_stake_account.amount,
+        );
+        self.to_pool_checkpoints.record(
+            self.to_pool.key(),
+            slot,
+            previous_to_total_stake,
+            self.to_pool.total_stake,
+        );
+        self.to_stake_checkpoints.record(
+            self.to_stake_account.key(),
+            slot,
+            previous_to_amount,
+            self.to_stake_account.amount,
+        );
+
+        emit!(EventMigrateStake {
+            user: self.user.key(),
+            from_pool: self.from_pool.key(),
+            to_pool: self.to_pool.key(),
+            migrate_amount: migrate_tokens,
+            stake_amount,
+        });
+        Ok(())
+    }
+}
@@ -0,0 +1,109 @@
+use crate::{helper, BalanceCheckpoints, Errors, StakeAccount, StakingPool};
+use anchor_lang::prelude::*;
+
+#[derive(Accounts)]
+pub struct RelocateStake<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(mut)]
+    pub rent_payer: Signer<'info>,
+
+    #[account(mut)]
+    pub staking_pool: Box<Account<'info, StakingPool>>,
+
+    #[account(
+        mut,
+        seeds = [
+            helper::STAKE_ACCOUNT_SEED,
+            &user.key().to_bytes(),
+        ],
+        bump,
+        has_one = staking_pool @Errors::InvalidStakeAccount,
+        has_one = user @Errors::InvalidStakeAccount,
+        close = user,
+    )]
+    pub legacy_stake_account: Box<Account<'info, StakeAccount>>,
+
+    #[account(
+        init_if_needed,
+        space = StakeAccount::SPACE,
+        payer = rent_payer,
+        rent_exempt = enforce,
+        seeds = [
+            helper::STAKE_ACCOUNT_SEED,
+            &staking_pool.key().to_bytes(),
+            &user.key().to_bytes(),
+        ],
+        bump,
+    )]
+    pub stake_account: Box<Account<'info, StakeAccount>>,
+
+    #[account(
+        init_if_needed,
+        space = 8 + std::mem::size_of::<BalanceCheckpoints>(),
+        payer = rent_payer,
+        rent_exempt = enforce,
+        seeds = [
+            helper::CHECKPOINTS_SEED,
+            &stake_account.key().to_bytes(),
+        ],
+        bump,
+    )]
+    pub stake_checkpoints: Box<Account<'info, BalanceCheckpoints>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[event]
+pub struct EventRelocateStake {
+    pub user: Pubkey,
+    pub staking_pool: Pubkey,
+    pub legacy_stake_account: Pubkey,
+    pub stake_account: Pubkey,
+    /// Stake units moved over.
+    pub amount: u64,
+}
+
+impl<'info> RelocateStake<'info> {
+    /// Moves a position opened before positions were scoped by pool to the
+    /// pool scoped PDA, where `stake` can top it up again. Into an empty PDA
+    /// the position moves unchanged. Otherwise both are settled and merged,
+    /// see `StakeAccount::merge`. The legacy account is closed to `user`.
+    pub fn process(&mut self) -> Result<()> {
+        let amount = self.legacy_stake_account.amount;
+        let previous_amount = self.stake_account.amount;
+
+        if self.stake_account.user == Pubkey::default() {
+            let stake_account: &mut StakeAccount = &mut self.stake_account;
+            *stake_account = (**self.legacy_stake_account).clone();
+        } else {
+            self.staking_pool.update_pool()?;
+            let current_time = Clock::get()?.unix_timestamp as u64;
+
+            self.legacy_stake_account
+                .update_reward(&self.staking_pool)?;
+            self.stake_account.update_reward(&self.staking_pool)?;
+            self.stake_account
+                .merge(&self.legacy_stake_account, current_time)?;
+            self.stake_account
+                .update_reward_debt(&mut self.staking_pool, current_time)?;
+        }
+
+        self.stake_checkpoints.record(
+            self.stake_account.key(),
+            Clock::get()?.slot,
+            previous_amount,
+            self.stake_account.amount,
+        );
+
+        emit!(EventRelocateStake {
+            user: self.user.key(),
+            staking_pool: self.staking_pool.key(),
+            legacy_stake_account: self.legacy_stake_account.key(),
+            stake_account: self.stake_account.key(),
+            amount,
+        });
+        Ok(())
+    }
+}
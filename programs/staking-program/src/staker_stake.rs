@@ -1,9 +1,7 @@
-use crate::{helper, Errors, StakeAccount, StakingPool};
+use crate::{helper, BalanceCheckpoints, Blocklist, Errors, StakeAccount, StakingPool};
 use anchor_lang::prelude::*;
 use anchor_spl::associated_token::AssociatedToken;
-use anchor_spl::token_interface::{
-    transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked,
-};
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked};
 
 #[derive(Accounts)]
 pub struct Stake<'info> {
@@ -38,17 +36,59 @@ pub struct Stake<'info> {
 
     #[account(
         init_if_needed,
-        space = 8 + std::mem::size_of::<StakeAccount>(),
+        space = StakeAccount::SPACE,
         payer = rent_payer,
         rent_exempt = enforce,
         seeds = [
             helper::STAKE_ACCOUNT_SEED,
+            &staking_pool.key().to_bytes(),
             &user.key().to_bytes(),
         ],
         bump,
     )]
     pub stake_account: Box<Account<'info, StakeAccount>>,
 
+    #[account(
+        init_if_needed,
+        space = 8 + std::mem::size_of::<BalanceCheckpoints>(),
+        payer = rent_payer,
+        rent_exempt = enforce,
+        seeds = [
+            helper::CHECKPOINTS_SEED,
+            &staking_pool.key().to_bytes(),
+        ],
+        bump,
+    )]
+    pub pool_checkpoints: Box<Account<'info, BalanceCheckpoints>>,
+
+    #[account(
+        init_if_needed,
+        space = 8 + std::mem::size_of::<BalanceCheckpoints>(),
+        payer = rent_payer,
+        rent_exempt = enforce,
+        seeds = [
+            helper::CHECKPOINTS_SEED,
+            &stake_account.key().to_bytes(),
+        ],
+        bump,
+    )]
+    pub stake_checkpoints: Box<Account<'info, BalanceCheckpoints>>,
+
+    /// CHECK: the instructions sysvar, for pools requiring attestations.
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    pub instructions_sysvar: UncheckedAccount<'info>,
+
+    /// CHECK: blocklist entry of `user`, which must not exist.
+    #[account(
+        seeds = [
+            helper::BLOCKLIST_SEED,
+            &staking_pool.key().to_bytes(),
+            &user.key().to_bytes(),
+        ],
+        bump,
+    )]
+    pub blocklist: UncheckedAccount<'info>,
+
     pub token_program: Interface<'info, TokenInterface>,
     pub associated_token_program: Program<'info, AssociatedToken>,
     pub system_program: Program<'info, System>,
@@ -62,12 +102,24 @@ pub struct EventStake {
 }
 
 impl<'info> Stake<'info> {
-    pub fn process(&mut self, stake_amount: u64) -> Result<()> {
-        require_gte!(
-            stake_amount,
-            self.staking_pool.min_stake_amount,
-            Errors::StakeAmountTooLow
-        );
+    pub fn process(
+        &mut self,
+        stake_amount: u64,
+        proof: Vec<[u8; 32]>,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        helper::check_transfer_hook_accounts(
+            self.staking_pool.extension_policy,
+            remaining_accounts,
+        )?;
+        Blocklist::check_not_blocked(&self.blocklist)?;
+        self.staking_pool
+            .check_allowlist(&self.user.key(), &proof)?;
+        self.staking_pool.check_attestation(
+            &self.staking_pool.key(),
+            &self.user.key(),
+            &self.instructions_sysvar,
+        )?;
 
         self.staking_pool.update_pool()?;
 
@@ -79,33 +131,45 @@ impl<'info> Stake<'info> {
                 to: self.pool_token_account.to_account_info(),
                 authority: self.user.to_account_info(),
             },
-        );
-        transfer_checked(
+        )
+        .with_remaining_accounts(remaining_accounts.to_vec());
+        let stake_amount = helper::transfer_checked_received(
             transfer_to_pool_cpi_context,
+            &mut self.pool_token_account,
             stake_amount,
             self.token_mint.decimals,
         )?;
 
-        self.staking_pool.total_stake += stake_amount;
-
-        if self.stake_account.user == Pubkey::default() {
-            self.stake_account.set_inner(StakeAccount {
-                staking_pool: self.staking_pool.key(),
-                user: self.user.key(),
-                amount: stake_amount,
-                reward: 0,
-                reward_debt: self.staking_pool.calc_reward_debt(stake_amount)?,
-                _reserved: [0u8; 128],
-            });
-        } else {
-            self.stake_account
-                .update_reward(self.staking_pool.reward_per_share)?;
-
-            self.stake_account.amount += stake_amount;
-
-            self.stake_account
-                .update_reward_debt(self.staking_pool.reward_per_share)?;
-        }
+        require_gte!(
+            stake_amount,
+            self.staking_pool.min_stake_amount,
+            Errors::StakeAmountTooLow
+        );
+
+        let previous_total_stake = self.staking_pool.total_stake;
+        let previous_amount = self.stake_account.amount;
+
+        let staking_pool_key = self.staking_pool.key();
+        self.stake_account.deposit(
+            &mut self.staking_pool,
+            staking_pool_key,
+            self.user.key(),
+            stake_amount,
+        )?;
+
+        let slot = Clock::get()?.slot;
+        self.pool_checkpoints.record(
+            self.staking_pool.key(),
+            slot,
+            previous_total_stake,
+            self.staking_pool.total_stake,
+        );
+        self.stake_checkpoints.record(
+            self.stake_account.key(),
+            slot,
+            previous_amount,
+            self.stake_account.amount,
+        );
 
         emit!(EventStake {
             staker: self.user.key(),
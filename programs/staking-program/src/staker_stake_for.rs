@@ -0,0 +1,199 @@
+use crate::{helper, BalanceCheckpoints, Blocklist, Errors, StakeAccount, StakingPool};
+use anchor_lang::prelude::*;
+use anchor_spl::associated_token::AssociatedToken;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked};
+
+#[derive(Accounts)]
+#[instruction(beneficiary: Pubkey)]
+pub struct StakeFor<'info> {
+    pub funder: Signer<'info>,
+
+    #[account(mut)]
+    pub rent_payer: Signer<'info>,
+
+    #[account(mut)]
+    pub staking_pool: Box<Account<'info, StakingPool>>,
+
+    #[account(
+        address = staking_pool.token_mint @Errors::TokenMintAccountNotMatch
+    )]
+    pub token_mint: Box<InterfaceAccount<'info, Mint>>,
+
+    #[account(
+        mut,
+        associated_token::mint = token_mint,
+        associated_token::authority = funder,
+        associated_token::token_program = token_program,
+    )]
+    pub funder_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        associated_token::mint = token_mint,
+        associated_token::authority = staking_pool,
+        associated_token::token_program = token_program,
+    )]
+    pub pool_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        init_if_needed,
+        space = StakeAccount::SPACE,
+        payer = rent_payer,
+        rent_exempt = enforce,
+        seeds = [
+            helper::STAKE_ACCOUNT_SEED,
+            &staking_pool.key().to_bytes(),
+            &beneficiary.to_bytes(),
+        ],
+        bump,
+    )]
+    pub stake_account: Box<Account<'info, StakeAccount>>,
+
+    #[account(
+        init_if_needed,
+        space = 8 + std::mem::size_of::<BalanceCheckpoints>(),
+        payer = rent_payer,
+        rent_exempt = enforce,
+        seeds = [
+            helper::CHECKPOINTS_SEED,
+            &staking_pool.key().to_bytes(),
+        ],
+        bump,
+    )]
+    pub pool_checkpoints: Box<Account<'info, BalanceCheckpoints>>,
+
+    #[account(
+        init_if_needed,
+        space = 8 + std::mem::size_of::<BalanceCheckpoints>(),
+        payer = rent_payer,
+        rent_exempt = enforce,
+        seeds = [
+            helper::CHECKPOINTS_SEED,
+            &stake_account.key().to_bytes(),
+        ],
+        bump,
+    )]
+    pub stake_checkpoints: Box<Account<'info, BalanceCheckpoints>>,
+
+    /// CHECK: the instructions sysvar, for pools requiring attestations.
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    pub instructions_sysvar: UncheckedAccount<'info>,
+
+    /// CHECK: blocklist entry of `beneficiary`, which must not exist.
+    #[account(
+        seeds = [
+            helper::BLOCKLIST_SEED,
+            &staking_pool.key().to_bytes(),
+            &beneficiary.to_bytes(),
+        ],
+        bump,
+    )]
+    pub blocklist: UncheckedAccount<'info>,
+
+    /// CHECK: blocklist entry of `funder`, which must not exist either.
+    #[account(
+        seeds = [
+            helper::BLOCKLIST_SEED,
+            &staking_pool.key().to_bytes(),
+            &funder.key().to_bytes(),
+        ],
+        bump,
+    )]
+    pub funder_blocklist: UncheckedAccount<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[event]
+pub struct EventStakeFor {
+    pub funder: Pubkey,
+    pub beneficiary: Pubkey,
+    pub stake_amount: u64,
+    pub staking_pool: Pubkey,
+}
+
+impl<'info> StakeFor<'info> {
+    /// Stakes tokens of `funder` into the position of `beneficiary`, who
+    /// alone can unstake or claim them. `proof` is for `beneficiary`. The
+    /// deposit ages from now, like one by `beneficiary` itself.
+    pub fn process(
+        &mut self,
+        beneficiary: Pubkey,
+        stake_amount: u64,
+        proof: Vec<[u8; 32]>,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        helper::check_transfer_hook_accounts(
+            self.staking_pool.extension_policy,
+            remaining_accounts,
+        )?;
+        Blocklist::check_not_blocked(&self.blocklist)?;
+        Blocklist::check_not_blocked(&self.funder_blocklist)?;
+        self.staking_pool.check_allowlist(&beneficiary, &proof)?;
+        self.staking_pool.check_attestation(
+            &self.staking_pool.key(),
+            &beneficiary,
+            &self.instructions_sysvar,
+        )?;
+
+        self.staking_pool.update_pool()?;
+
+        let transfer_to_pool_cpi_context = CpiContext::new(
+            self.token_program.to_account_info(),
+            TransferChecked {
+                from: self.funder_token_account.to_account_info(),
+                mint: self.token_mint.to_account_info(),
+                to: self.pool_token_account.to_account_info(),
+                authority: self.funder.to_account_info(),
+            },
+        )
+        .with_remaining_accounts(remaining_accounts.to_vec());
+        let stake_amount = helper::transfer_checked_received(
+            transfer_to_pool_cpi_context,
+            &mut self.pool_token_account,
+            stake_amount,
+            self.token_mint.decimals,
+        )?;
+
+        require_gte!(
+            stake_amount,
+            self.staking_pool.min_stake_amount,
+            Errors::StakeAmountTooLow
+        );
+
+        let previous_total_stake = self.staking_pool.total_stake;
+        let previous_amount = self.stake_account.amount;
+
+        let staking_pool_key = self.staking_pool.key();
+        self.stake_account.deposit(
+            &mut self.staking_pool,
+            staking_pool_key,
+            beneficiary,
+            stake_amount,
+        )?;
+
+        let slot = Clock::get()?.slot;
+        self.pool_checkpoints.record(
+            self.staking_pool.key(),
+            slot,
+            previous_total_stake,
+            self.staking_pool.total_stake,
+        );
+        self.stake_checkpoints.record(
+            self.stake_account.key(),
+            slot,
+            previous_amount,
+            self.stake_account.amount,
+        );
+
+        emit!(EventStakeFor {
+            funder: self.funder.key(),
+            beneficiary,
+            stake_amount,
+            staking_pool: self.staking_pool.key(),
+        });
+        Ok(())
+    }
+}
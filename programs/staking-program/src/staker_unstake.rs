@@ -1,4 +1,6 @@
-use crate::{Errors, StakeAccount, StakingPool, UnstakeAccount};
+use crate::{
+    helper, BalanceCheckpoints, Blocklist, Errors, StakeAccount, StakingPool, UnstakeAccount,
+};
 use anchor_lang::prelude::*;
 
 #[derive(Accounts)]
@@ -26,6 +28,43 @@ pub struct Unstake<'info> {
     )]
     pub unstake_account: Box<Account<'info, UnstakeAccount>>,
 
+    #[account(
+        init_if_needed,
+        space = 8 + std::mem::size_of::<BalanceCheckpoints>(),
+        payer = rent_payer,
+        rent_exempt = enforce,
+        seeds = [
+            helper::CHECKPOINTS_SEED,
+            &staking_pool.key().to_bytes(),
+        ],
+        bump,
+    )]
+    pub pool_checkpoints: Box<Account<'info, BalanceCheckpoints>>,
+
+    #[account(
+        init_if_needed,
+        space = 8 + std::mem::size_of::<BalanceCheckpoints>(),
+        payer = rent_payer,
+        rent_exempt = enforce,
+        seeds = [
+            helper::CHECKPOINTS_SEED,
+            &stake_account.key().to_bytes(),
+        ],
+        bump,
+    )]
+    pub stake_checkpoints: Box<Account<'info, BalanceCheckpoints>>,
+
+    /// CHECK: blocklist entry of `user`, which must not freeze it.
+    #[account(
+        seeds = [
+            helper::BLOCKLIST_SEED,
+            &staking_pool.key().to_bytes(),
+            &user.key().to_bytes(),
+        ],
+        bump,
+    )]
+    pub blocklist: UncheckedAccount<'info>,
+
     pub system_program: Program<'info, System>,
 }
 
@@ -39,6 +78,7 @@ pub struct EventUnstake {
 
 impl<'info> Unstake<'info> {
     pub fn process(&mut self, unstake_amount: u64) -> Result<()> {
+        Blocklist::check_not_frozen(&self.blocklist)?;
         require_gt!(unstake_amount, 0, Errors::UnstakeAmountIsZero);
 
         require_gte!(
@@ -47,30 +87,67 @@ impl<'info> Unstake<'info> {
             Errors::BalanceNotEnough
         );
 
+        let current_time = Clock::get()?.unix_timestamp as u64;
+        require_gte!(
+            current_time,
+            self.stake_account
+                .stake_timestamp
+                .saturating_add(self.staking_pool.min_stake_duration),
+            Errors::StakeDurationNotReached
+        );
+        require_gte!(
+            self.stake_account.amount - unstake_amount,
+            self.stake_account.locked_amount(current_time),
+            Errors::StakeAmountLocked
+        );
+
         self.staking_pool.update_pool()?;
 
-        self.stake_account
-            .update_reward(self.staking_pool.reward_per_share)?;
+        self.stake_account.update_reward(&self.staking_pool)?;
 
-        self.stake_account.amount -= unstake_amount;
+        let previous_total_stake = self.staking_pool.total_stake;
+        let previous_amount = self.stake_account.amount;
 
         self.stake_account
-            .update_reward_debt(self.staking_pool.reward_per_share)?;
+            .remove_amount(unstake_amount, current_time)?;
+
+        self.stake_account
+            .update_reward_debt(&mut self.staking_pool, current_time)?;
+
+        let unstake_tokens = self.staking_pool.stake_to_tokens(unstake_amount)?;
+        self.staking_pool.total_stake -= unstake_amount;
+        self.staking_pool.total_unbonding += unstake_tokens;
 
-        let current_time = Clock::get()?.unix_timestamp as u64;
         self.unstake_account.set_inner(UnstakeAccount {
             staking_pool: self.staking_pool.key(),
             user: self.user.key(),
-            amount: unstake_amount,
+            amount: unstake_tokens,
             withdrawable_timestamp: current_time + self.staking_pool.unbonding_seconds,
-            _reserved: [0u8; 128],
+            unbonding_scale: self.staking_pool.effective_unbonding_scale(),
+            linear: self.staking_pool.linear_unbonding,
+            unstake_timestamp: current_time,
+            withdrawn_amount: 0,
+            _reserved: [0u8; 95],
         });
-        self.staking_pool.total_stake -= unstake_amount;
+
+        let slot = Clock::get()?.slot;
+        self.pool_checkpoints.record(
+            self.staking_pool.key(),
+            slot,
+            previous_total_stake,
+            self.staking_pool.total_stake,
+        );
+        self.stake_checkpoints.record(
+            self.stake_account.key(),
+            slot,
+            previous_amount,
+            self.stake_account.amount,
+        );
 
         emit!(EventUnstake {
             staker: self.user.key(),
             unstake_account: self.unstake_account.key(),
-            unstake_amount,
+            unstake_amount: unstake_tokens,
             staking_pool: self.staking_pool.key(),
         });
 
@@ -0,0 +1,137 @@
+use crate::{helper, Errors, StakeAccount, StakingPool, UnstakeAccount, VoterWeightRecord};
+use anchor_lang::prelude::*;
+
+#[derive(Accounts)]
+pub struct UpdateVoterWeightRecord<'info> {
+    #[account(mut)]
+    pub rent_payer: Signer<'info>,
+
+    pub staking_pool: Box<Account<'info, StakingPool>>,
+
+    #[account(
+        seeds = [
+            helper::STAKE_ACCOUNT_SEED,
+            &staking_pool.key().to_bytes(),
+            &stake_account.user.to_bytes(),
+        ],
+        bump,
+        has_one = staking_pool @Errors::InvalidStakeAccount,
+    )]
+    pub stake_account: Box<Account<'info, StakeAccount>>,
+
+    /// CHECK: only recorded in the voter weight record, spl-governance matches
+    /// it against the realm the weight is used in.
+    pub realm: UncheckedAccount<'info>,
+
+    #[account(
+        init_if_needed,
+        space = 8 + std::mem::size_of::<VoterWeightRecord>(),
+        payer = rent_payer,
+        rent_exempt = enforce,
+        seeds = [
+            helper::VOTER_WEIGHT_RECORD_SEED,
+            &realm.key().to_bytes(),
+            &staking_pool.key().to_bytes(),
+            &stake_account.user.to_bytes(),
+        ],
+        bump,
+    )]
+    pub voter_weight_record: Box<Account<'info, VoterWeightRecord>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[event]
+pub struct EventUpdateVoterWeightRecord {
+    pub user: Pubkey,
+    pub realm: Pubkey,
+    pub voter_weight: u64,
+    pub staking_pool: Pubkey,
+}
+
+impl<'info> UpdateVoterWeightRecord<'info> {
+    /// The weight is the staked token amount of the user's pool scoped
+    /// position, boosted by the current loyalty multiplier when
+    /// `include_loyalty`. Legacy positions must be relocated first. Unstake
+    /// accounts of the user may be passed as remaining accounts to count
+    /// funds that are still locked, which are never boosted.
+    pub fn process(
+        &mut self,
+        include_unbonding: bool,
+        include_loyalty: bool,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        if !include_unbonding {
+            require!(
+                remaining_accounts.is_empty(),
+                Errors::RemainingAccountsNotMatch
+            );
+        }
+
+        let current_time = Clock::get()?.unix_timestamp as u64;
+        let mut stake_account = (**self.stake_account).clone();
+        stake_account.migrate();
+        let amount = if include_loyalty {
+            self.staking_pool.calc_weight(
+                stake_account.amount,
+                current_time.saturating_sub(stake_account.loyalty_timestamp),
+            )?
+        } else {
+            stake_account.amount
+        };
+        let mut voter_weight = self.staking_pool.stake_to_tokens(amount)?;
+
+        let mut counted: Vec<Pubkey> = Vec::with_capacity(remaining_accounts.len());
+        for account_info in remaining_accounts {
+            require!(
+                !counted.contains(account_info.key),
+                Errors::RemainingAccountsNotMatch
+            );
+            counted.push(account_info.key());
+
+            require_keys_eq!(
+                *account_info.owner,
+                crate::ID,
+                Errors::InvalidUnstakeAccount
+            );
+            let unstake_account =
+                UnstakeAccount::try_deserialize(&mut &account_info.try_borrow_data()?[..])?;
+            require_keys_eq!(
+                unstake_account.staking_pool,
+                self.staking_pool.key(),
+                Errors::InvalidUnstakeAccount
+            );
+            require_keys_eq!(
+                unstake_account.user,
+                self.stake_account.user,
+                Errors::InvalidUnstakeAccount
+            );
+
+            voter_weight = voter_weight
+                .checked_add(unstake_account.locked_amount(&self.staking_pool, current_time)?)
+                .ok_or_else(|| error!(Errors::CalculationFail))?;
+        }
+
+        // Expiring at the current slot forces the weight to be refreshed in
+        // the same transaction that uses it.
+        self.voter_weight_record.set_inner(VoterWeightRecord {
+            realm: self.realm.key(),
+            governing_token_mint: self.staking_pool.token_mint,
+            governing_token_owner: self.stake_account.user,
+            voter_weight,
+            voter_weight_expiry: Some(Clock::get()?.slot),
+            weight_action: None,
+            weight_action_target: None,
+            reserved: [0u8; 8],
+        });
+
+        emit!(EventUpdateVoterWeightRecord {
+            user: self.stake_account.user,
+            realm: self.realm.key(),
+            voter_weight,
+            staking_pool: self.staking_pool.key(),
+        });
+
+        Ok(())
+    }
+}
@@ -1,9 +1,7 @@
 use crate::{helper, Errors, StakingPool, UnstakeAccount};
 use anchor_lang::prelude::*;
 use anchor_spl::associated_token::AssociatedToken;
-use anchor_spl::token_interface::{
-    transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked,
-};
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked};
 
 #[derive(Accounts)]
 pub struct Withdraw<'info> {
@@ -15,9 +13,9 @@ pub struct Withdraw<'info> {
     #[account(mut)]
     pub staking_pool: Box<Account<'info, StakingPool>>,
 
+    /// Closed to `rent_payer` once fully withdrawn.
     #[account(
         mut,
-        close = rent_payer,
         has_one = staking_pool @Errors::InvalidUnstakeAccount,
         has_one = user @Errors::InvalidUnstakeAccount,
     )]
@@ -59,20 +57,31 @@ pub struct EventWithdraw {
 }
 
 impl<'info> Withdraw<'info> {
-    pub fn process(&mut self) -> Result<()> {
+    pub fn process(&mut self, remaining_accounts: &[AccountInfo<'info>]) -> Result<()> {
+        helper::check_transfer_hook_accounts(
+            self.staking_pool.extension_policy,
+            remaining_accounts,
+        )?;
+
         let timestamp = Clock::get()?.unix_timestamp as u64;
         require_gt!(
-            self.unstake_account.amount,
+            self.unstake_account.amount - self.unstake_account.withdrawn_amount,
             0,
             Errors::UnstakeAccountAmountZero
         );
-        require_gte!(
-            timestamp,
-            self.unstake_account.withdrawable_timestamp,
-            Errors::UnstakeAccountNotClaimable
-        );
 
-        let withdraw_amount = self.unstake_account.amount;
+        let unlocked_amount =
+            self.unstake_account.unlocked_amount(timestamp) - self.unstake_account.withdrawn_amount;
+        require_gt!(unlocked_amount, 0, Errors::UnstakeAccountNotClaimable);
+
+        let withdraw_amount = self
+            .unstake_account
+            .to_tokens(&self.staking_pool, unlocked_amount)?;
+        self.staking_pool.release_unbonding(
+            &self.unstake_account,
+            withdraw_amount,
+            self.pool_token_account.amount,
+        )?;
 
         require_gte!(
             self.pool_token_account.amount,
@@ -80,9 +89,9 @@ impl<'info> Withdraw<'info> {
             Errors::PoolBalanceNotEnough
         );
 
-        self.unstake_account.amount = 0;
+        self.unstake_account.withdrawn_amount += unlocked_amount;
 
-        transfer_checked(
+        helper::transfer_checked(
             CpiContext::new_with_signer(
                 self.token_program.to_account_info(),
                 TransferChecked {
@@ -98,11 +107,17 @@ impl<'info> Withdraw<'info> {
                     &[self.staking_pool.index],
                     &[self.staking_pool.pool_seed_bump],
                 ]],
-            ),
+            )
+            .with_remaining_accounts(remaining_accounts.to_vec()),
             withdraw_amount,
             self.token_mint.decimals,
         )?;
 
+        if self.unstake_account.withdrawn_amount == self.unstake_account.amount {
+            self.unstake_account
+                .close(self.rent_payer.to_account_info())?;
+        }
+
         emit!(EventWithdraw {
             user: self.user.key(),
             unstake_account: self.unstake_account.key(),
@@ -17,72 +17,611 @@ pub struct StakingPool {
 
     /// For FixedPerTokenPerSecond: per staked smallest unit per second.
     ///
-    ///     Reward rate is scaled by 1e12 to support fractional values.
-    ///     Reward rate is in **smallest token unit per second(after scaling)**.
+    /// - Reward rate is scaled by 1e12 to support fractional values.
+    /// - Reward rate is in **smallest token unit per second(after scaling)**.
     ///
     /// For FixedTotalPerSecond: total reward per second in smallest units.
     ///
-    ///     Reward rate is in **smallest token unit per second**.
+    /// - Reward rate is in **smallest token unit per second**.
     pub reward_rate: u64,
     pub reward_algorithm: RewardAlgorithm,
 
+    /// Sum of `StakeAccount::amount`, in stake units. See `stake_scale`.
     pub total_stake: u64,
     pub total_reward: u64,
     pub undistributed_reward: u64,
     pub last_reward_timestamp: u64,
     pub reward_per_share: u128,
 
-    /// Reserved space for future upgrades. Do not use.
-    pub _reserved: [u8; 256],
+    /// Bit set of `helper::ALLOW_*` flags for mint extensions the admin
+    /// accepted at creation.
+    pub extension_policy: u8,
+
+    /// Account allowed to slash the pool, default when slashing is disabled.
+    pub slasher: Pubkey,
+    /// Token value of one stake unit, scaled by `helper::SLASH_SCALE_BASE`.
+    /// Slashing lowers it so every position loses the same share. Zero for
+    /// pools that were never slashed.
+    pub stake_scale: u128,
+    /// Same as `stake_scale`, for tokens in unstake accounts.
+    pub unbonding_scale: u128,
+    /// Token value of all unstake accounts subject to `unbonding_scale`.
+    pub total_unbonding: u64,
+    /// Rewards distributed by `update_pool` and not yet claimed. Rewards
+    /// distributed before this was tracked are paid from the untracked
+    /// balance, see `untracked_balance`, and missing here until
+    /// `owed_rewards_tracked`.
+    pub total_owed_rewards: u64,
+
+    /// Layout version, `helper::POOL_VERSION` once `migrate` ran.
+    ///
+    /// - 0: `reward_per_share` is still scaled by `helper::REWARD_CALC_BASE`.
+    /// - 1: `total_weight` is not tracked yet.
+    pub version: u8,
+    /// Truncated part of the last `FixedPerTokenPerSecond` emission, scaled by
+    /// `helper::REWARD_CALC_BASE`, or of the last uncapped APR based one,
+    /// scaled by `helper::BPS_BASE * helper::SECONDS_PER_YEAR`, added to the
+    /// next one.
+    pub reward_remainder: u64,
+    /// Truncated part of the last `reward_per_share` increase, scaled by
+    /// `helper::REWARD_PER_SHARE_BASE`, added to the next one.
+    pub reward_per_share_remainder: u64,
+
+    /// Seconds a position must stay staked, by its average deposit time,
+    /// before it can be unstaked.
+    pub min_stake_duration: u64,
+
+    /// Reward multiplier reached after `loyalty_ramp_seconds` of continuous
+    /// stake, scaled by `helper::LOYALTY_MULTIPLIER_BASE`. Zero disables it.
+    pub max_loyalty_multiplier: u64,
+    pub loyalty_ramp_seconds: u64,
+    /// Sum of `StakeAccount::weight`. Rewards are shared by weight.
+    pub total_weight: u64,
+
+    /// New unstake accounts release their funds linearly over
+    /// `unbonding_seconds` instead of all at once at the end.
+    pub linear_unbonding: bool,
+
+    /// Merkle root of the wallets allowed to stake, see
+    /// `helper::allowlist_leaf`. All zero when staking is open to anyone.
+    pub allowlist_root: [u8; 32],
+    /// Signer of the attestations required to stake, see
+    /// `helper::check_attestation`. Default when none are required.
+    pub attestor: Pubkey,
+
+    /// Future reward changes by increasing `effective_timestamp`, applied by
+    /// `update_pool_at` at their exact timestamp. Reads as empty from the
+    /// zeroed reserve of older pools, which are grown to `SPACE` when an
+    /// entry is scheduled.
+    pub reward_schedule: Vec<ScheduledRewardChange>,
+
+    /// Seconds per reward epoch, `helper::SOLANA_EPOCHS` to follow Solana
+    /// epochs, or zero for continuous rewards. In epoch mode `reward_rate` is
+    /// paid per epoch, by `settle_epochs`, to the weight staked through the
+    /// whole epoch, and `reward_algorithm` is unused.
+    pub epoch_length: u64,
+    /// Part of `total_weight` added during the open epoch, which only earns
+    /// from the next one on.
+    pub pending_weight: u64,
+    /// `reward_per_share` after the last settled epochs, oldest first. The
+    /// last entry is the last settled epoch.
+    pub epoch_history: Vec<SettledEpoch>,
+
+    /// Whether `total_owed_rewards` covers every unclaimed reward: set at
+    /// creation, or for older pools by `backfill_owed_rewards`.
+    pub owed_rewards_tracked: bool,
+
+    /// Reserved space for future upgrades. Do not use. Shrunk by the largest
+    /// `RewardAlgorithm` payload, so pools keep fitting their account
+    /// whichever algorithm they use.
+    pub _reserved: [u8; 4],
 }
 
 #[derive(Clone, Copy, Debug, AnchorSerialize, AnchorDeserialize, PartialEq, Eq)]
 pub enum RewardAlgorithm {
     FixedPerTokenPerSecond,
     FixedTotalPerSecond,
+    /// Total reward per second of `reward_rate` until `anchor_timestamp`,
+    /// then halving every `half_life_seconds`: in steps, or smoothly when
+    /// `continuous`.
+    DecayingTotalPerSecond {
+        anchor_timestamp: u64,
+        half_life_seconds: u64,
+        continuous: bool,
+    },
+    /// Pays `apr_bps` a year on the staked tokens, at most `max_per_second`
+    /// in total unless zero. `reward_rate` is unused.
+    TargetApr {
+        apr_bps: u64,
+        max_per_second: u64,
+    },
+    /// Pays an APR on the staked tokens that falls with the utilization, the
+    /// staked tokens over `target_stake` in basis points: linearly from
+    /// `max_apr_bps` when empty to `kink_apr_bps` at `kink_utilization_bps`,
+    /// then by `slope_bps` per 100% of utilization, down to `min_apr_bps`.
+    /// `reward_rate` is unused. Rates are `u32` to fit the reserved space.
+    UtilizationApr {
+        target_stake: u64,
+        kink_utilization_bps: u32,
+        max_apr_bps: u32,
+        kink_apr_bps: u32,
+        slope_bps: u32,
+        min_apr_bps: u32,
+    },
+}
+
+impl RewardAlgorithm {
+    pub fn check(&self) -> Result<()> {
+        match *self {
+            RewardAlgorithm::DecayingTotalPerSecond {
+                half_life_seconds, ..
+            } => {
+                require_gt!(half_life_seconds, 0, Errors::ParamsNotMatch);
+            }
+            RewardAlgorithm::UtilizationApr {
+                target_stake,
+                kink_utilization_bps,
+                max_apr_bps,
+                kink_apr_bps,
+                min_apr_bps,
+                ..
+            } => {
+                require_gt!(target_stake, 0, Errors::ParamsNotMatch);
+                require_gt!(kink_utilization_bps, 0, Errors::ParamsNotMatch);
+                require!(
+                    max_apr_bps >= kink_apr_bps && kink_apr_bps >= min_apr_bps,
+                    Errors::ParamsNotMatch
+                );
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, AnchorSerialize, AnchorDeserialize, PartialEq, Eq)]
+pub struct SettledEpoch {
+    pub epoch: u64,
+    pub reward_per_share: u128,
+}
+
+#[derive(Clone, Copy, Debug, AnchorSerialize, AnchorDeserialize, PartialEq, Eq)]
+pub struct ScheduledRewardChange {
+    pub effective_timestamp: u64,
+    pub reward_rate: u64,
+    pub reward_algorithm: RewardAlgorithm,
 }
 
 impl StakingPool {
-    pub fn calc_new_reward(&self, time_diff: u64) -> Result<u64> {
+    /// Account size with room for a full `reward_schedule` and
+    /// `epoch_history`.
+    pub const SPACE: usize = 8
+        + std::mem::size_of::<StakingPool>()
+        + helper::REWARD_SCHEDULE_CAPACITY * std::mem::size_of::<ScheduledRewardChange>()
+        + helper::EPOCH_HISTORY_CAPACITY * std::mem::size_of::<SettledEpoch>();
+
+    /// Checks `proof` places `user` on the allowlist, if the pool has one.
+    pub fn check_allowlist(&self, user: &Pubkey, proof: &[[u8; 32]]) -> Result<()> {
+        if self.allowlist_root == [0u8; 32] {
+            return Ok(());
+        }
+
+        let root = proof
+            .iter()
+            .fold(helper::allowlist_leaf(user), |node, sibling| {
+                helper::allowlist_node(&node, sibling)
+            });
+        require!(root == self.allowlist_root, Errors::NotAllowlisted);
+
+        Ok(())
+    }
+
+    /// Checks the transaction carries an attestation for `user`, if the pool
+    /// requires one.
+    pub fn check_attestation(
+        &self,
+        staking_pool_key: &Pubkey,
+        user: &Pubkey,
+        instructions_sysvar: &AccountInfo,
+    ) -> Result<()> {
+        if self.attestor == Pubkey::default() {
+            return Ok(());
+        }
+
+        helper::check_attestation(instructions_sysvar, &self.attestor, staking_pool_key, user)
+    }
+
+    pub fn effective_stake_scale(&self) -> u128 {
+        if self.stake_scale == 0 {
+            helper::SLASH_SCALE_BASE
+        } else {
+            self.stake_scale
+        }
+    }
+
+    pub fn effective_unbonding_scale(&self) -> u128 {
+        if self.unbonding_scale == 0 {
+            helper::SLASH_SCALE_BASE
+        } else {
+            self.unbonding_scale
+        }
+    }
+
+    /// Token value of `amount` stake units.
+    pub fn stake_to_tokens(&self, amount: u64) -> Result<u64> {
+        u64::try_from((amount as u128) * self.effective_stake_scale() / helper::SLASH_SCALE_BASE)
+            .map_err(|_| error!(Errors::CalculationFail))
+    }
+
+    /// Stake units bought by `tokens`, rounded down.
+    pub fn tokens_to_stake(&self, tokens: u64) -> Result<u64> {
+        u64::try_from((tokens as u128) * helper::SLASH_SCALE_BASE / self.effective_stake_scale())
+            .map_err(|_| error!(Errors::CalculationFail))
+    }
+
+    pub fn total_stake_tokens(&self) -> Result<u64> {
+        self.stake_to_tokens(self.total_stake)
+    }
+
+    /// Tokens the pool vault must hold for staked principal, tracked
+    /// unbonding funds, owed rewards and rewards yet to be distributed.
+    pub fn tracked_balance(&self) -> Result<u64> {
+        [
+            self.total_unbonding,
+            self.total_owed_rewards,
+            self.undistributed_reward,
+        ]
+        .iter()
+        .try_fold(self.total_stake_tokens()?, |sum, amount| {
+            sum.checked_add(*amount)
+        })
+        .ok_or_else(|| error!(Errors::CalculationFail))
+    }
+
+    /// Part of `vault_balance` not backing any tracked bucket. It pays for
+    /// liabilities created before the buckets were tracked.
+    pub fn untracked_balance(&self, vault_balance: u64) -> Result<u64> {
+        Ok(vault_balance.saturating_sub(self.tracked_balance()?))
+    }
+
+    /// Releases `amount` of claimed rewards from `total_owed_rewards`, taking
+    /// any legacy remainder from the untracked balance.
+    pub fn release_owed_rewards(&mut self, amount: u64, vault_balance: u64) -> Result<()> {
+        let tracked = amount.min(self.total_owed_rewards);
+        require_gte!(
+            self.untracked_balance(vault_balance)?,
+            amount - tracked,
+            Errors::PoolBalanceNotEnough
+        );
+        self.total_owed_rewards -= tracked;
+
+        Ok(())
+    }
+
+    /// Releases `amount` paid out for `unstake_account` from `total_unbonding`,
+    /// or from the untracked balance for legacy unstake accounts.
+    pub fn release_unbonding(
+        &mut self,
+        unstake_account: &UnstakeAccount,
+        amount: u64,
+        vault_balance: u64,
+    ) -> Result<()> {
+        if unstake_account.unbonding_scale == 0 {
+            require_gte!(
+                self.untracked_balance(vault_balance)?,
+                amount,
+                Errors::PoolBalanceNotEnough
+            );
+        } else {
+            // Rounding in `UnstakeAccount::to_tokens` only ever leaves dust behind.
+            self.total_unbonding = self.total_unbonding.saturating_sub(amount);
+        }
+
+        Ok(())
+    }
+
+    /// Returns the reward emitted over `time_diff` seconds and the truncated
+    /// remainder to carry into the next call.
+    pub fn calc_new_reward(&self, time_diff: u64) -> Result<(u64, u64)> {
         match self.reward_algorithm {
-            RewardAlgorithm::FixedPerTokenPerSecond => u64::try_from(
-                (self.total_stake as u128) * (time_diff as u128) * (self.reward_rate as u128)
-                    / helper::REWARD_CALC_BASE,
-            )
-            .map_err(|_| error!(Errors::CalculationFail)),
-            RewardAlgorithm::FixedTotalPerSecond => {
+            RewardAlgorithm::FixedPerTokenPerSecond => {
+                let scaled_reward = (self.total_stake_tokens()? as u128)
+                    .checked_mul(time_diff as u128)
+                    .and_then(|value| value.checked_mul(self.reward_rate as u128))
+                    .and_then(|value| value.checked_add(self.reward_remainder as u128))
+                    .ok_or_else(|| error!(Errors::CalculationFail))?;
+
+                Ok((
+                    u64::try_from(scaled_reward / helper::REWARD_CALC_BASE)
+                        .map_err(|_| error!(Errors::CalculationFail))?,
+                    (scaled_reward % helper::REWARD_CALC_BASE) as u64,
+                ))
+            }
+            RewardAlgorithm::FixedTotalPerSecond => Ok((
                 u64::try_from((time_diff as u128) * (self.reward_rate as u128))
-                    .map_err(|_| error!(Errors::CalculationFail))
+                    .map_err(|_| error!(Errors::CalculationFail))?,
+                0,
+            )),
+            RewardAlgorithm::DecayingTotalPerSecond {
+                anchor_timestamp,
+                half_life_seconds,
+                continuous,
+            } => {
+                let start = self.last_reward_timestamp;
+                let end = start + time_diff;
+                let undecayed = (end.min(anchor_timestamp).saturating_sub(start) as u128)
+                    * (self.reward_rate as u128);
+                let emission = |timestamp: u64| {
+                    helper::decayed_emission(
+                        self.reward_rate,
+                        half_life_seconds,
+                        continuous,
+                        timestamp.saturating_sub(anchor_timestamp),
+                    )
+                };
+                let decayed = emission(end)?.saturating_sub(emission(start)?);
+
+                Ok((
+                    u64::try_from(undecayed + decayed)
+                        .map_err(|_| error!(Errors::CalculationFail))?,
+                    0,
+                ))
+            }
+            RewardAlgorithm::TargetApr {
+                apr_bps,
+                max_per_second,
+            } => {
+                let (reward, reward_remainder) = self.calc_apr_reward(apr_bps, time_diff)?;
+
+                let max_reward = (max_per_second as u128) * (time_diff as u128);
+                if max_per_second > 0 && (reward as u128) > max_reward {
+                    return Ok((
+                        u64::try_from(max_reward).map_err(|_| error!(Errors::CalculationFail))?,
+                        0,
+                    ));
+                }
+
+                Ok((reward, reward_remainder))
+            }
+            RewardAlgorithm::UtilizationApr {
+                target_stake,
+                kink_utilization_bps,
+                max_apr_bps,
+                kink_apr_bps,
+                slope_bps,
+                min_apr_bps,
+            } => {
+                let utilization_bps = (self.total_stake_tokens()? as u128)
+                    * (helper::BPS_BASE as u128)
+                    / (target_stake as u128);
+                let kink_utilization_bps = kink_utilization_bps as u128;
+                let apr_bps = if utilization_bps <= kink_utilization_bps {
+                    max_apr_bps as u128
+                        - ((max_apr_bps - kink_apr_bps) as u128) * utilization_bps
+                            / kink_utilization_bps
+                } else {
+                    (kink_apr_bps as u128)
+                        .saturating_sub(
+                            (slope_bps as u128) * (utilization_bps - kink_utilization_bps)
+                                / (helper::BPS_BASE as u128),
+                        )
+                        .max(min_apr_bps as u128)
+                };
+
+                self.calc_apr_reward(apr_bps as u64, time_diff)
             }
         }
     }
 
-    pub fn calc_reward_per_share(&self, reward: u64) -> Result<u128> {
-        Ok(
-            (reward as u128) * helper::REWARD_CALC_BASE / (self.total_stake as u128)
-                + self.reward_per_share,
+    /// Reward of `apr_bps` a year on the staked tokens over `time_diff`, with
+    /// the truncated remainder scaled by `helper::BPS_BASE *
+    /// helper::SECONDS_PER_YEAR`.
+    fn calc_apr_reward(&self, apr_bps: u64, time_diff: u64) -> Result<(u64, u64)> {
+        let divisor = (helper::BPS_BASE as u128) * (helper::SECONDS_PER_YEAR as u128);
+        let scaled_reward = (self.total_stake_tokens()? as u128)
+            .checked_mul(apr_bps as u128)
+            .and_then(|value| value.checked_mul(time_diff as u128))
+            .and_then(|value| value.checked_add(self.reward_remainder as u128))
+            .ok_or_else(|| error!(Errors::CalculationFail))?;
+
+        Ok((
+            u64::try_from(scaled_reward / divisor).map_err(|_| error!(Errors::CalculationFail))?,
+            (scaled_reward % divisor) as u64,
+        ))
+    }
+
+    /// Returns the new `reward_per_share` after distributing `reward` over
+    /// `weight`, and the truncated remainder to carry into the next call.
+    pub fn calc_reward_per_share(&self, reward: u64, weight: u64) -> Result<(u128, u64)> {
+        let scaled_reward = (reward as u128) * helper::REWARD_PER_SHARE_BASE
+            + (self.reward_per_share_remainder as u128);
+
+        Ok((
+            scaled_reward / (weight as u128) + self.reward_per_share,
+            (scaled_reward % (weight as u128)) as u64,
+        ))
+    }
+
+    pub fn calc_reward_debt(&mut self, weight: u64) -> Result<u128> {
+        helper::reward_for(weight, self.reward_per_share)
+    }
+
+    /// Upgrades a pool created by an earlier program version. Stake account
+    /// reward debts are token amounts and stay valid.
+    pub fn migrate(&mut self) -> Result<()> {
+        if self.version < 1 {
+            self.reward_per_share = self
+                .reward_per_share
+                .checked_mul(helper::REWARD_PER_SHARE_BASE / helper::REWARD_CALC_BASE)
+                .ok_or_else(|| error!(Errors::CalculationFail))?;
+        }
+        if self.version < 2 {
+            // Every existing position has a 1x weight until it is synced.
+            self.total_weight = self.total_stake;
+        }
+        self.version = helper::POOL_VERSION;
+
+        Ok(())
+    }
+
+    /// Multiplier for a position staked for `age` seconds, scaled by
+    /// `helper::LOYALTY_MULTIPLIER_BASE`. It ramps linearly from 1x to
+    /// `max_loyalty_multiplier` over `loyalty_ramp_seconds`.
+    pub fn loyalty_multiplier(&self, age: u64) -> u64 {
+        if self.max_loyalty_multiplier <= helper::LOYALTY_MULTIPLIER_BASE {
+            return helper::LOYALTY_MULTIPLIER_BASE;
+        }
+        if age >= self.loyalty_ramp_seconds {
+            return self.max_loyalty_multiplier;
+        }
+
+        helper::LOYALTY_MULTIPLIER_BASE
+            + ((self.max_loyalty_multiplier - helper::LOYALTY_MULTIPLIER_BASE) as u128
+                * (age as u128)
+                / (self.loyalty_ramp_seconds as u128)) as u64
+    }
+
+    /// Reward weight of `amount` stake units staked for `age` seconds.
+    pub fn calc_weight(&self, amount: u64, age: u64) -> Result<u64> {
+        u64::try_from(
+            (amount as u128) * (self.loyalty_multiplier(age) as u128)
+                / (helper::LOYALTY_MULTIPLIER_BASE as u128),
         )
+        .map_err(|_| error!(Errors::CalculationFail))
     }
 
-    pub fn calc_reward_debt(&mut self, amount: u64) -> Result<u64> {
-        u64::try_from(self.reward_per_share * (amount as u128) / helper::REWARD_CALC_BASE)
-            .map_err(|_| error!(Errors::CalculationFail))
+    /// Algorithms scale `reward_remainder` differently, so it is dropped when
+    /// switching between them.
+    pub fn set_reward_algorithm(&mut self, reward_algorithm: RewardAlgorithm) {
+        if std::mem::discriminant(&reward_algorithm)
+            != std::mem::discriminant(&self.reward_algorithm)
+        {
+            self.reward_remainder = 0;
+        }
+        self.reward_algorithm = reward_algorithm;
     }
 
     pub fn update_pool(&mut self) -> Result<()> {
-        let current_time = Clock::get()?.unix_timestamp as u64;
+        self.update_pool_with_clock(&Clock::get()?)
+    }
+
+    /// Settles the epochs ended by `clock`, then accrues continuous rewards
+    /// up to it.
+    pub fn update_pool_with_clock(&mut self, clock: &Clock) -> Result<()> {
+        self.settle_epochs(self.epoch_at(clock))?;
+
+        self.update_pool_at(clock.unix_timestamp as u64)
+    }
+
+    pub fn is_epoch_mode(&self) -> bool {
+        self.epoch_length != 0
+    }
 
+    pub fn epoch_at(&self, clock: &Clock) -> u64 {
+        match self.epoch_length {
+            0 => 0,
+            helper::SOLANA_EPOCHS => clock.epoch,
+            epoch_length => clock.unix_timestamp as u64 / epoch_length,
+        }
+    }
+
+    /// First epoch not settled yet, which new weight starts pending in.
+    pub fn open_epoch(&self) -> u64 {
+        self.epoch_history
+            .last()
+            .map_or(0, |settled| settled.epoch + 1)
+    }
+
+    /// `reward_per_share` once `epoch` was settled, `None` if it wasn't yet.
+    /// Epochs dropped from `epoch_history` resolve to the oldest kept one,
+    /// which pays less rather than more.
+    pub fn settled_reward_per_share(&self, epoch: u64) -> Option<u128> {
+        let index = self
+            .epoch_history
+            .partition_point(|settled| settled.epoch < epoch);
+
+        self.epoch_history
+            .get(index)
+            .map(|settled| settled.reward_per_share)
+    }
+
+    /// Pays `reward_rate` for each epoch ended before `current_epoch` to the
+    /// weight staked through all of it.
+    pub fn settle_epochs(&mut self, current_epoch: u64) -> Result<()> {
+        if !self.is_epoch_mode() {
+            return Ok(());
+        }
+        self.migrate()?;
+
+        while self.open_epoch() < current_epoch {
+            // Once pending weight joined in, nothing changes until the next
+            // stake instruction settles again, so the remaining epochs are
+            // settled together.
+            let epoch = if self.pending_weight > 0 {
+                self.open_epoch()
+            } else {
+                current_epoch - 1
+            };
+            let epochs = epoch + 1 - self.open_epoch();
+
+            let eligible_weight = self.total_weight - self.pending_weight;
+            if eligible_weight > 0 {
+                let reward = ((self.reward_rate as u128) * (epochs as u128))
+                    .min(self.undistributed_reward as u128) as u64;
+                if reward > 0 {
+                    self.undistributed_reward -= reward;
+                    (self.reward_per_share, self.reward_per_share_remainder) =
+                        self.calc_reward_per_share(reward, eligible_weight)?;
+                    self.total_owed_rewards += reward;
+                }
+            }
+            self.pending_weight = 0;
+
+            if self.epoch_history.len() >= helper::EPOCH_HISTORY_CAPACITY {
+                self.epoch_history.remove(0);
+            }
+            self.epoch_history.push(SettledEpoch {
+                epoch,
+                reward_per_share: self.reward_per_share,
+            });
+        }
+
+        Ok(())
+    }
+
+    pub fn update_pool_at(&mut self, current_time: u64) -> Result<()> {
+        self.migrate()?;
+
+        // Accrue up to each due change first, so both sides of its boundary
+        // are paid at their own rate.
+        while let Some(change) = self.reward_schedule.first().copied() {
+            if change.effective_timestamp > current_time {
+                break;
+            }
+
+            self.accrue_until(change.effective_timestamp)?;
+            self.reward_rate = change.reward_rate;
+            self.set_reward_algorithm(change.reward_algorithm);
+            self.reward_schedule.remove(0);
+        }
+
+        self.accrue_until(current_time)
+    }
+
+    fn accrue_until(&mut self, current_time: u64) -> Result<()> {
         if current_time <= self.last_reward_timestamp {
             return Ok(());
         }
 
-        if self.total_stake == 0 {
+        if self.total_weight == 0 || self.is_epoch_mode() {
             self.last_reward_timestamp = current_time;
             return Ok(());
         }
 
         let time_diff = current_time - self.last_reward_timestamp;
-        let mut reward = self.calc_new_reward(time_diff)?;
+        let (mut reward, reward_remainder) = self.calc_new_reward(time_diff)?;
+        self.reward_remainder = reward_remainder;
 
         if reward > 0 {
             if self.undistributed_reward >= reward {
@@ -90,9 +629,12 @@ impl StakingPool {
             } else {
                 reward = self.undistributed_reward;
                 self.undistributed_reward = 0;
+                self.reward_remainder = 0;
             }
 
-            self.reward_per_share = self.calc_reward_per_share(reward)?;
+            (self.reward_per_share, self.reward_per_share_remainder) =
+                self.calc_reward_per_share(reward, self.total_weight)?;
+            self.total_owed_rewards += reward;
         }
 
         self.last_reward_timestamp = current_time;
@@ -101,35 +643,292 @@ impl StakingPool {
     }
 }
 
+/// A user's position in a pool, at the PDA of `[STAKE_ACCOUNT_SEED,
+/// staking_pool, user]`. Accounts opened before positions were scoped by pool
+/// live at `[STAKE_ACCOUNT_SEED, user]` and can still be unstaked, claimed and
+/// migrated, or moved to the pool scoped PDA by `relocate_stake` to take new
+/// stake.
 #[account]
 #[derive(Debug)]
 pub struct StakeAccount {
     pub staking_pool: Pubkey,
     pub user: Pubkey,
+    /// Stake units, see `StakingPool::stake_to_tokens` for their token value.
     pub amount: u64,
-    pub reward: u64,
-    pub reward_debt: u64,
+    /// `reward` and `reward_debt` of accounts created before they were
+    /// widened, moved over by `migrate`.
+    pub legacy_reward: u64,
+    pub legacy_reward_debt: u64,
+
+    /// Part of `amount` granted by the admin under a vesting schedule. It
+    /// unlocks linearly from `vesting_start_timestamp` to
+    /// `vesting_end_timestamp`, but nothing unlocks before the cliff.
+    pub vesting_amount: u64,
+    pub vesting_start_timestamp: u64,
+    pub vesting_cliff_timestamp: u64,
+    pub vesting_end_timestamp: u64,
+
+    /// Layout version, `helper::STAKE_ACCOUNT_VERSION` once `migrate` ran.
+    ///
+    /// - 0: still using `legacy_reward` and `legacy_reward_debt`.
+    /// - 1: `weight` and `loyalty_timestamp` are not set yet.
+    pub version: u8,
+    pub reward: u128,
+    pub reward_debt: u128,
+
+    /// Average deposit time of `amount`, weighted by the amounts deposited.
+    /// Zero for accounts predating it, which count as staked long ago.
+    pub stake_timestamp: u64,
+    /// Start of the continuous stake age driving the loyalty multiplier.
+    /// Deposits average it like `stake_timestamp`, unstaking moves it
+    /// forward in proportion to the share unstaked.
+    pub loyalty_timestamp: u64,
+    /// `amount` boosted by the loyalty multiplier, as of the last sync.
+    pub weight: u64,
+    /// Part of `weight` added during `pending_epoch` of an epoch mode pool,
+    /// which earns from the end of that epoch on.
+    pub pending_weight: u64,
+    pub pending_epoch: u64,
 
     /// Reserved space for future upgrades. Do not use.
-    pub _reserved: [u8; 128],
+    pub _reserved: [u8; 23],
 }
 
 impl StakeAccount {
-    pub fn update_reward(&mut self, reward_per_share: u128) -> Result<()> {
-        self.reward = u64::try_from(
-            (self.amount as u128) * reward_per_share / helper::REWARD_CALC_BASE
-                + (self.reward as u128)
-                - (self.reward_debt as u128),
-        )
-        .map_err(|_| error!(Errors::CalculationFail))?;
+    /// Account size including the discriminator. Spelled out rather than
+    /// taken from `size_of`, which changes with the alignment of the `u128`
+    /// fields, because existing accounts must keep passing the
+    /// `init_if_needed` size check.
+    pub const SPACE: usize = 8 + 32 * 2 + 8 * 7 + 1 + 16 * 2 + 8 * 5 + 23;
+
+    /// Credits `tokens` of new stake to this account, opening the position
+    /// for `user` if it is empty, and returns the stake units bought. The
+    /// pool must be updated beforehand.
+    ///
+    /// Whoever funds it, the deposit is averaged into the timestamps of an
+    /// existing position, so new capital never inherits an older stake age.
+    pub fn deposit(
+        &mut self,
+        staking_pool: &mut StakingPool,
+        staking_pool_key: Pubkey,
+        user: Pubkey,
+        tokens: u64,
+    ) -> Result<u64> {
+        let amount = staking_pool.tokens_to_stake(tokens)?;
+        require_gt!(amount, 0, Errors::StakeAmountTooLow);
+
+        staking_pool.total_stake += amount;
+
+        let current_time = Clock::get()?.unix_timestamp as u64;
+        if self.user == Pubkey::default() {
+            staking_pool.total_weight += amount;
+            let pending_weight = if staking_pool.is_epoch_mode() {
+                staking_pool.pending_weight += amount;
+                amount
+            } else {
+                0
+            };
+
+            *self = StakeAccount {
+                staking_pool: staking_pool_key,
+                user,
+                amount,
+                legacy_reward: 0,
+                legacy_reward_debt: 0,
+                vesting_amount: 0,
+                vesting_start_timestamp: 0,
+                vesting_cliff_timestamp: 0,
+                vesting_end_timestamp: 0,
+                version: helper::STAKE_ACCOUNT_VERSION,
+                reward: 0,
+                reward_debt: staking_pool.calc_reward_debt(amount - pending_weight)?,
+                stake_timestamp: current_time,
+                loyalty_timestamp: current_time,
+                weight: amount,
+                pending_weight,
+                pending_epoch: staking_pool.open_epoch(),
+                _reserved: [0u8; 23],
+            };
+        } else {
+            self.update_reward(staking_pool)?;
+
+            self.add_amount(amount, current_time)?;
+
+            self.update_reward_debt(staking_pool, current_time)?;
+        }
+
+        Ok(amount)
+    }
+
+    /// Adds `amount` stake units deposited at `current_time`, moving
+    /// `stake_timestamp` and `loyalty_timestamp` to the weighted average of
+    /// both deposits.
+    pub fn add_amount(&mut self, amount: u64, current_time: u64) -> Result<()> {
+        let total = (self.amount as u128) + (amount as u128);
+        let average = |timestamp: u64| {
+            u64::try_from(
+                ((self.amount as u128) * (timestamp as u128)
+                    + (amount as u128) * (current_time as u128))
+                    / total,
+            )
+            .map_err(|_| error!(Errors::CalculationFail))
+        };
+        self.stake_timestamp = average(self.stake_timestamp)?;
+        self.loyalty_timestamp = average(self.loyalty_timestamp)?;
+        self.amount = u64::try_from(total).map_err(|_| error!(Errors::CalculationFail))?;
 
         Ok(())
     }
 
-    pub fn update_reward_debt(&mut self, reward_per_share: u128) -> Result<()> {
-        self.reward_debt =
-            u64::try_from((self.amount as u128) * reward_per_share / helper::REWARD_CALC_BASE)
-                .map_err(|_| error!(Errors::CalculationFail))?;
+    /// Moves the position of `other` into this one, averaging the timestamps
+    /// by amount. Both must have run `update_reward`, and `update_reward_debt`
+    /// must run afterwards. Only one of them may still have locked vesting.
+    pub fn merge(&mut self, other: &StakeAccount, current_time: u64) -> Result<()> {
+        require!(
+            self.locked_amount(current_time) == 0 || other.locked_amount(current_time) == 0,
+            Errors::StakeAmountLocked
+        );
+        if other.locked_amount(current_time) > 0 {
+            self.vesting_amount = other.vesting_amount;
+            self.vesting_start_timestamp = other.vesting_start_timestamp;
+            self.vesting_cliff_timestamp = other.vesting_cliff_timestamp;
+            self.vesting_end_timestamp = other.vesting_end_timestamp;
+        }
+
+        let total = (self.amount as u128) + (other.amount as u128);
+        if total > 0 {
+            let average = |timestamp: u64, other_timestamp: u64| {
+                u64::try_from(
+                    ((self.amount as u128) * (timestamp as u128)
+                        + (other.amount as u128) * (other_timestamp as u128))
+                        / total,
+                )
+                .map_err(|_| error!(Errors::CalculationFail))
+            };
+            self.stake_timestamp = average(self.stake_timestamp, other.stake_timestamp)?;
+            self.loyalty_timestamp = average(self.loyalty_timestamp, other.loyalty_timestamp)?;
+        }
+        self.amount = u64::try_from(total).map_err(|_| error!(Errors::CalculationFail))?;
+
+        // Pending weight left after `update_reward` is in the open epoch.
+        if other.pending_weight > 0 {
+            self.pending_epoch = self.pending_epoch.max(other.pending_epoch);
+        }
+        self.weight += other.weight;
+        self.pending_weight += other.pending_weight;
+        self.reward = self
+            .reward
+            .checked_add(other.reward)
+            .ok_or_else(|| error!(Errors::CalculationFail))?;
+
+        Ok(())
+    }
+
+    /// Removes `amount` stake units at `current_time`. The loyalty age
+    /// shrinks by the share of the position removed.
+    pub fn remove_amount(&mut self, amount: u64, current_time: u64) -> Result<()> {
+        let age = current_time.saturating_sub(self.loyalty_timestamp);
+        self.loyalty_timestamp += ((age as u128) * (amount as u128) / (self.amount as u128)) as u64;
+        self.amount -= amount;
+
+        Ok(())
+    }
+
+    /// Part of the vesting grant that is still locked at `current_time`.
+    pub fn locked_amount(&self, current_time: u64) -> u64 {
+        if current_time < self.vesting_cliff_timestamp {
+            return self.vesting_amount;
+        }
+        if current_time >= self.vesting_end_timestamp {
+            return 0;
+        }
+
+        let vested = (self.vesting_amount as u128)
+            * ((current_time - self.vesting_start_timestamp) as u128)
+            / ((self.vesting_end_timestamp - self.vesting_start_timestamp) as u128);
+        self.vesting_amount - vested as u64
+    }
+
+    /// Upgrades an account created by an earlier program version.
+    pub fn migrate(&mut self) {
+        if self.version < 1 {
+            self.reward = self.legacy_reward as u128;
+            self.reward_debt = self.legacy_reward_debt as u128;
+            self.legacy_reward = 0;
+            self.legacy_reward_debt = 0;
+        }
+        if self.version < 2 {
+            // Matches the 1x weight `StakingPool::migrate` assumes.
+            self.weight = self.amount;
+            self.loyalty_timestamp = self.stake_timestamp;
+        }
+        self.version = helper::STAKE_ACCOUNT_VERSION;
+    }
+
+    pub fn update_reward(&mut self, staking_pool: &StakingPool) -> Result<()> {
+        self.migrate();
+
+        // Pending weight of a settled epoch earns from the end of it on.
+        if self.pending_weight > 0 {
+            if let Some(reward_per_share) =
+                staking_pool.settled_reward_per_share(self.pending_epoch)
+            {
+                self.reward_debt = helper::reward_for(self.pending_weight, reward_per_share)?
+                    .checked_add(self.reward_debt)
+                    .ok_or_else(|| error!(Errors::CalculationFail))?;
+                self.pending_weight = 0;
+            }
+        }
+
+        self.reward = helper::reward_for(
+            self.weight - self.pending_weight,
+            staking_pool.reward_per_share,
+        )?
+        .checked_add(self.reward)
+        .and_then(|value| value.checked_sub(self.reward_debt))
+        .ok_or_else(|| error!(Errors::CalculationFail))?;
+
+        Ok(())
+    }
+
+    /// Syncs `weight` with the current loyalty multiplier, updating the pool
+    /// `total_weight`, and resets the reward debt. Call after `update_reward`.
+    /// In epoch mode added weight is pending until the open epoch is settled,
+    /// and removed weight is taken from the pending part first.
+    pub fn update_reward_debt(
+        &mut self,
+        staking_pool: &mut StakingPool,
+        current_time: u64,
+    ) -> Result<()> {
+        self.migrate();
+
+        let weight = staking_pool.calc_weight(
+            self.amount,
+            current_time.saturating_sub(self.loyalty_timestamp),
+        )?;
+        staking_pool.total_weight = staking_pool
+            .total_weight
+            .checked_sub(self.weight)
+            .and_then(|total_weight| total_weight.checked_add(weight))
+            .ok_or_else(|| error!(Errors::CalculationFail))?;
+        if staking_pool.is_epoch_mode() {
+            if weight > self.weight {
+                let added = weight - self.weight;
+                self.pending_weight += added;
+                self.pending_epoch = staking_pool.open_epoch();
+                staking_pool.pending_weight += added;
+            } else {
+                let removed = (self.weight - weight).min(self.pending_weight);
+                self.pending_weight -= removed;
+                staking_pool.pending_weight -= removed;
+            }
+        }
+        self.weight = weight;
+
+        self.reward_debt = helper::reward_for(
+            self.weight - self.pending_weight,
+            staking_pool.reward_per_share,
+        )?;
 
         Ok(())
     }
@@ -140,9 +939,1014 @@ impl StakeAccount {
 pub struct UnstakeAccount {
     pub staking_pool: Pubkey,
     pub user: Pubkey,
+    /// Token amount at the time of unstaking.
     pub amount: u64,
+    /// End of unbonding, when all of `amount` is unlocked.
     pub withdrawable_timestamp: u64,
 
+    /// `StakingPool::unbonding_scale` when the account was created. Zero for
+    /// accounts predating slashing, which are not subject to it.
+    pub unbonding_scale: u128,
+
+    /// Whether `amount` unlocks linearly from `unstake_timestamp` to
+    /// `withdrawable_timestamp` rather than all at the end.
+    pub linear: bool,
+    pub unstake_timestamp: u64,
+    /// Part of `amount` already paid out, before any slashing.
+    pub withdrawn_amount: u64,
+
+    /// Reserved space for future upgrades. Do not use.
+    pub _reserved: [u8; 95],
+}
+
+impl UnstakeAccount {
+    /// Part of `amount` unlocked at `current_time`, including what was
+    /// already withdrawn.
+    pub fn unlocked_amount(&self, current_time: u64) -> u64 {
+        if current_time >= self.withdrawable_timestamp {
+            return self.amount;
+        }
+        if !self.linear || current_time <= self.unstake_timestamp {
+            return 0;
+        }
+
+        ((self.amount as u128) * ((current_time - self.unstake_timestamp) as u128)
+            / ((self.withdrawable_timestamp - self.unstake_timestamp) as u128)) as u64
+    }
+
+    /// Tokens paid out for `amount` of this account, after any slashing of
+    /// unbonding funds.
+    pub fn to_tokens(&self, staking_pool: &StakingPool, amount: u64) -> Result<u64> {
+        if self.unbonding_scale == 0 {
+            return Ok(amount);
+        }
+
+        u64::try_from(
+            (amount as u128) * staking_pool.effective_unbonding_scale() / self.unbonding_scale,
+        )
+        .map_err(|_| error!(Errors::CalculationFail))
+    }
+
+    /// Tokens still owed, unlocked or not.
+    pub fn remaining_amount(&self, staking_pool: &StakingPool) -> Result<u64> {
+        self.to_tokens(staking_pool, self.amount - self.withdrawn_amount)
+    }
+
+    /// Tokens not unlocked yet at `current_time`, which can't have been
+    /// withdrawn and moved elsewhere.
+    pub fn locked_amount(&self, staking_pool: &StakingPool, current_time: u64) -> Result<u64> {
+        self.to_tokens(
+            staking_pool,
+            self.amount - self.unlocked_amount(current_time),
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, AnchorSerialize, AnchorDeserialize, PartialEq, Eq)]
+pub struct Checkpoint {
+    pub slot: u64,
+    pub amount: u64,
+}
+
+/// Ring buffer of balance checkpoints, kept for a stake account (its amount)
+/// or a staking pool (its total stake). Balances are in stake units, so
+/// slashing leaves them unchanged and user balances keep adding up to the
+/// pool total. See `StakingPool::stake_to_tokens` for their token value.
+#[account]
+#[derive(Debug)]
+pub struct BalanceCheckpoints {
+    pub owner: Pubkey,
+    /// Balances before this slot are unknown, they predate the checkpoints.
+    pub start_slot: u64,
+    /// Index the next checkpoint is written to.
+    pub head: u16,
+    pub len: u16,
+    pub checkpoints: [Checkpoint; helper::CHECKPOINTS_CAPACITY],
+}
+
+impl BalanceCheckpoints {
+    /// Records that the balance of `owner` changed from `previous_amount`
+    /// to `amount` in `slot`.
+    pub fn record(&mut self, owner: Pubkey, slot: u64, previous_amount: u64, amount: u64) {
+        if self.owner == Pubkey::default() {
+            self.owner = owner;
+            if previous_amount > 0 {
+                self.start_slot = slot;
+            }
+        }
+
+        self.push(slot, amount);
+    }
+
+    fn push(&mut self, slot: u64, amount: u64) {
+        if let Some(latest) = self.latest_mut() {
+            if latest.slot == slot {
+                latest.amount = amount;
+                return;
+            }
+        }
+
+        self.checkpoints[self.head as usize] = Checkpoint { slot, amount };
+        self.head = ((self.head as usize + 1) % helper::CHECKPOINTS_CAPACITY) as u16;
+        if (self.len as usize) < helper::CHECKPOINTS_CAPACITY {
+            self.len += 1;
+        }
+    }
+
+    fn latest_mut(&mut self) -> Option<&mut Checkpoint> {
+        if self.len == 0 {
+            return None;
+        }
+
+        let index =
+            (self.head as usize + helper::CHECKPOINTS_CAPACITY - 1) % helper::CHECKPOINTS_CAPACITY;
+        Some(&mut self.checkpoints[index])
+    }
+
+    /// Balance at the end of `slot`.
+    pub fn amount_at(&self, slot: u64) -> Result<u64> {
+        require_gte!(slot, self.start_slot, Errors::CheckpointNotFound);
+
+        for i in 1..=self.len as usize {
+            let index = (self.head as usize + helper::CHECKPOINTS_CAPACITY - i)
+                % helper::CHECKPOINTS_CAPACITY;
+            let checkpoint = &self.checkpoints[index];
+            if checkpoint.slot <= slot {
+                return Ok(checkpoint.amount);
+            }
+        }
+
+        // Older checkpoints were overwritten once the buffer wrapped.
+        require!(
+            (self.len as usize) < helper::CHECKPOINTS_CAPACITY,
+            Errors::CheckpointNotFound
+        );
+
+        Ok(0)
+    }
+}
+
+/// SPL Governance voter weight addin record. The anchor discriminator of this
+/// account matches `VoterWeightRecord::ACCOUNT_DISCRIMINATOR` of the addin API,
+/// so realms can load it as a voter weight plugin record.
+#[account]
+#[derive(Debug)]
+pub struct VoterWeightRecord {
+    pub realm: Pubkey,
+    pub governing_token_mint: Pubkey,
+    pub governing_token_owner: Pubkey,
+    pub voter_weight: u64,
+    pub voter_weight_expiry: Option<u64>,
+    pub weight_action: Option<VoterWeightAction>,
+    pub weight_action_target: Option<Pubkey>,
+
+    /// Reserved by the addin API layout.
+    pub reserved: [u8; 8],
+}
+
+#[derive(Clone, Copy, Debug, AnchorSerialize, AnchorDeserialize, PartialEq, Eq)]
+pub enum VoterWeightAction {
+    CastVote,
+    CommentProposal,
+    CreateGovernance,
+    CreateProposal,
+    SignOffProposal,
+}
+
+/// Opt-in of both pool admins to let stakers move stake from `from_pool` to
+/// `to_pool` without unbonding. PDA of `[MIGRATION_LINK_SEED, from_pool,
+/// to_pool]`.
+#[account]
+#[derive(Debug)]
+pub struct MigrationLink {
+    pub from_pool: Pubkey,
+    pub to_pool: Pubkey,
+    pub from_pool_approved: bool,
+    pub to_pool_approved: bool,
+
     /// Reserved space for future upgrades. Do not use.
-    pub _reserved: [u8; 128],
+    pub _reserved: [u8; 64],
+}
+
+impl MigrationLink {
+    pub fn is_approved(&self) -> bool {
+        self.from_pool_approved && self.to_pool_approved
+    }
+}
+
+/// Admin block of `user` in `staking_pool`: no staking or claiming, and no
+/// unstaking either while `frozen`. PDA of `[BLOCKLIST_SEED, staking_pool,
+/// user]`, passed unchecked to staker instructions and only read when it
+/// exists.
+#[account]
+#[derive(Debug)]
+pub struct Blocklist {
+    pub staking_pool: Pubkey,
+    pub user: Pubkey,
+    /// Issuer defined code recorded for the block.
+    pub reason: u16,
+    pub frozen: bool,
+
+    /// Reserved space for future upgrades. Do not use.
+    pub _reserved: [u8; 64],
+}
+
+impl Blocklist {
+    pub fn check_not_blocked(blocklist: &AccountInfo) -> Result<()> {
+        require!(blocklist.data_is_empty(), Errors::UserBlocked);
+
+        Ok(())
+    }
+
+    pub fn check_not_frozen(blocklist: &AccountInfo) -> Result<()> {
+        if blocklist.data_is_empty() {
+            return Ok(());
+        }
+
+        let data = blocklist.try_borrow_data()?;
+        let blocklist = Blocklist::try_deserialize(&mut &data[..])?;
+        require!(!blocklist.frozen, Errors::StakeAccountFrozen);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn empty_checkpoints() -> BalanceCheckpoints {
+        BalanceCheckpoints {
+            owner: Pubkey::default(),
+            start_slot: 0,
+            head: 0,
+            len: 0,
+            checkpoints: [Checkpoint::default(); helper::CHECKPOINTS_CAPACITY],
+        }
+    }
+
+    fn pool(reward_algorithm: RewardAlgorithm, reward_rate: u64) -> StakingPool {
+        StakingPool {
+            creator: Pubkey::default(),
+            index: 0,
+            admin: Pubkey::default(),
+            pending_admin: Pubkey::default(),
+            pool_seed_bump: 0,
+            token_mint: Pubkey::default(),
+            min_stake_amount: 0,
+            unbonding_seconds: 0,
+            reward_rate,
+            reward_algorithm,
+            total_stake: 0,
+            total_reward: u64::MAX,
+            undistributed_reward: u64::MAX,
+            last_reward_timestamp: 0,
+            reward_per_share: 0,
+            extension_policy: 0,
+            slasher: Pubkey::default(),
+            stake_scale: 0,
+            unbonding_scale: 0,
+            total_unbonding: 0,
+            total_owed_rewards: 0,
+            version: helper::POOL_VERSION,
+            reward_remainder: 0,
+            reward_per_share_remainder: 0,
+            min_stake_duration: 0,
+            max_loyalty_multiplier: 0,
+            loyalty_ramp_seconds: 0,
+            total_weight: 0,
+            linear_unbonding: false,
+            allowlist_root: [0u8; 32],
+            attestor: Pubkey::default(),
+            reward_schedule: vec![],
+            epoch_length: 0,
+            pending_weight: 0,
+            epoch_history: vec![],
+            owed_rewards_tracked: true,
+            _reserved: [0u8; 4],
+        }
+    }
+
+    /// Position of `amount` units in `staking_pool`, as `deposit` opens it.
+    fn stake_account(staking_pool: &mut StakingPool, amount: u64) -> StakeAccount {
+        staking_pool.total_stake += amount;
+        staking_pool.total_weight += amount;
+
+        StakeAccount {
+            staking_pool: Pubkey::default(),
+            user: Pubkey::new_unique(),
+            amount,
+            legacy_reward: 0,
+            legacy_reward_debt: 0,
+            vesting_amount: 0,
+            vesting_start_timestamp: 0,
+            vesting_cliff_timestamp: 0,
+            vesting_end_timestamp: 0,
+            version: helper::STAKE_ACCOUNT_VERSION,
+            reward: 0,
+            reward_debt: staking_pool.calc_reward_debt(amount).unwrap(),
+            stake_timestamp: 0,
+            loyalty_timestamp: 0,
+            weight: amount,
+            pending_weight: 0,
+            pending_epoch: 0,
+            _reserved: [0u8; 23],
+        }
+    }
+
+    #[test]
+    fn allowlist_open_without_root() {
+        let staking_pool = pool(RewardAlgorithm::FixedTotalPerSecond, 0);
+
+        assert!(staking_pool
+            .check_allowlist(&Pubkey::new_unique(), &[])
+            .is_ok());
+    }
+
+    #[test]
+    fn allowlist_checks_proof() {
+        // Tree of four users: ((a, b), (c, d)).
+        let users: Vec<Pubkey> = (0..4).map(|_| Pubkey::new_unique()).collect();
+        let leaves: Vec<[u8; 32]> = users.iter().map(helper::allowlist_leaf).collect();
+        let left = helper::allowlist_node(&leaves[0], &leaves[1]);
+        let right = helper::allowlist_node(&leaves[2], &leaves[3]);
+        let mut staking_pool = pool(RewardAlgorithm::FixedTotalPerSecond, 0);
+        staking_pool.allowlist_root = helper::allowlist_node(&left, &right);
+
+        assert!(staking_pool
+            .check_allowlist(&users[0], &[leaves[1], right])
+            .is_ok());
+        assert!(staking_pool
+            .check_allowlist(&users[3], &[leaves[2], left])
+            .is_ok());
+
+        let not_allowlisted = error!(Errors::NotAllowlisted);
+        // Someone else's proof, a truncated one, and an outsider.
+        assert_eq!(
+            staking_pool
+                .check_allowlist(&users[1], &[leaves[1], right])
+                .unwrap_err(),
+            not_allowlisted
+        );
+        assert_eq!(
+            staking_pool
+                .check_allowlist(&users[0], &[leaves[1]])
+                .unwrap_err(),
+            not_allowlisted
+        );
+        assert_eq!(
+            staking_pool
+                .check_allowlist(&Pubkey::new_unique(), &[leaves[1], right])
+                .unwrap_err(),
+            not_allowlisted
+        );
+        // An inner node can't pass for a leaf.
+        assert_eq!(
+            staking_pool
+                .check_allowlist(&Pubkey::new_from_array(left), &[right])
+                .unwrap_err(),
+            not_allowlisted
+        );
+    }
+
+    /// Kink at 80% utilization and 1000 bps, losing 2500 bps per 100% past
+    /// it.
+    fn utilization_apr(
+        kink_utilization_bps: u32,
+        max_apr_bps: u32,
+        min_apr_bps: u32,
+    ) -> RewardAlgorithm {
+        RewardAlgorithm::UtilizationApr {
+            target_stake: 1_000,
+            kink_utilization_bps,
+            max_apr_bps,
+            kink_apr_bps: 1_000,
+            slope_bps: 2_500,
+            min_apr_bps,
+        }
+    }
+
+    /// Reward of a year of `utilization_apr(8_000, 2_000, 200)` with `stake` tokens staked.
+    fn utilization_apr_reward(stake: u64) -> u64 {
+        let mut staking_pool = pool(utilization_apr(8_000, 2_000, 200), 0);
+        staking_pool.total_stake = stake;
+
+        staking_pool
+            .calc_new_reward(helper::SECONDS_PER_YEAR)
+            .unwrap()
+            .0
+    }
+
+    #[test]
+    fn utilization_apr_below_kink() {
+        assert_eq!(utilization_apr_reward(0), 0);
+        // 40% utilization: 2000 - 1000 * 4000 / 8000 = 1500 bps.
+        assert_eq!(utilization_apr_reward(400), 60);
+    }
+
+    #[test]
+    fn utilization_apr_at_kink() {
+        assert_eq!(utilization_apr_reward(800), 80);
+    }
+
+    #[test]
+    fn utilization_apr_above_kink() {
+        // 100% utilization: 1000 - 2500 * 2000 / 10000 = 500 bps.
+        assert_eq!(utilization_apr_reward(1_000), 50);
+        // 200% utilization would be below the floor of 200 bps.
+        assert_eq!(utilization_apr_reward(2_000), 40);
+    }
+
+    #[test]
+    fn utilization_apr_check() {
+        assert!(utilization_apr(8_000, 2_000, 200).check().is_ok());
+
+        for invalid in [
+            utilization_apr(0, 2_000, 200),
+            utilization_apr(8_000, 999, 200),
+            utilization_apr(8_000, 2_000, 1_001),
+        ] {
+            assert_eq!(invalid.check().unwrap_err(), error!(Errors::ParamsNotMatch));
+        }
+    }
+
+    /// Pool paying 1000 tokens per epoch of 100 seconds, switched to epochs
+    /// during epoch 10.
+    fn epoch_pool() -> StakingPool {
+        let mut staking_pool = pool(RewardAlgorithm::FixedTotalPerSecond, 1_000);
+        staking_pool.epoch_length = 100;
+        staking_pool.epoch_history = vec![SettledEpoch {
+            epoch: 9,
+            reward_per_share: 0,
+        }];
+        staking_pool
+    }
+
+    #[test]
+    fn settle_epochs_pays_ended_epochs() {
+        let mut staking_pool = epoch_pool();
+        stake_account(&mut staking_pool, 1_000);
+
+        // The open epoch hasn't ended yet.
+        staking_pool.settle_epochs(10).unwrap();
+        assert_eq!(staking_pool.open_epoch(), 10);
+        assert_eq!(staking_pool.reward_per_share, 0);
+
+        // Without pending weight the ended epochs are settled at once.
+        staking_pool.settle_epochs(12).unwrap();
+        assert_eq!(staking_pool.open_epoch(), 12);
+        assert_eq!(staking_pool.epoch_history.len(), 2);
+        assert_eq!(
+            staking_pool.reward_per_share,
+            2 * helper::REWARD_PER_SHARE_BASE
+        );
+        assert_eq!(staking_pool.total_owed_rewards, 2_000);
+        assert_eq!(staking_pool.undistributed_reward, u64::MAX - 2_000);
+
+        // Capped by the undistributed rewards.
+        staking_pool.undistributed_reward = 500;
+        staking_pool.settle_epochs(13).unwrap();
+        assert_eq!(staking_pool.total_owed_rewards, 2_500);
+        assert_eq!(staking_pool.undistributed_reward, 0);
+        assert_eq!(
+            staking_pool.settled_reward_per_share(12),
+            Some(5 * helper::REWARD_PER_SHARE_BASE / 2)
+        );
+    }
+
+    #[test]
+    fn pending_weight_earns_from_next_epoch() {
+        let mut staking_pool = epoch_pool();
+        let mut early = stake_account(&mut staking_pool, 1_000);
+
+        // Staked during epoch 10, so pending until it is settled.
+        let mut late = stake_account(&mut staking_pool, 0);
+        late.amount = 1_000;
+        staking_pool.total_stake += 1_000;
+        late.update_reward_debt(&mut staking_pool, 0).unwrap();
+        assert_eq!((late.pending_weight, late.pending_epoch), (1_000, 10));
+        assert_eq!(staking_pool.pending_weight, 1_000);
+
+        // Epoch 10 goes to `early` alone, then epoch 11 is shared.
+        staking_pool.settle_epochs(12).unwrap();
+        assert_eq!(staking_pool.pending_weight, 0);
+        assert_eq!(
+            staking_pool.settled_reward_per_share(10),
+            Some(helper::REWARD_PER_SHARE_BASE)
+        );
+        assert_eq!(
+            staking_pool.reward_per_share,
+            3 * helper::REWARD_PER_SHARE_BASE / 2
+        );
+
+        early.update_reward(&staking_pool).unwrap();
+        late.update_reward(&staking_pool).unwrap();
+        assert_eq!(early.reward, 1_500);
+        assert_eq!(late.reward, 500);
+        assert_eq!(late.pending_weight, 0);
+        assert_eq!(
+            early.reward + late.reward,
+            staking_pool.total_owed_rewards as u128
+        );
+    }
+
+    /// `amount` tokens unbonding from 100 to 200, under the pool's
+    /// unslashed unbonding scale.
+    fn unstake_account(amount: u64, linear: bool) -> UnstakeAccount {
+        UnstakeAccount {
+            staking_pool: Pubkey::default(),
+            user: Pubkey::default(),
+            amount,
+            withdrawable_timestamp: 200,
+            unbonding_scale: helper::SLASH_SCALE_BASE,
+            linear,
+            unstake_timestamp: 100,
+            withdrawn_amount: 0,
+            _reserved: [0u8; 95],
+        }
+    }
+
+    #[test]
+    fn locked_unbonding_amount() {
+        let mut staking_pool = pool(RewardAlgorithm::FixedTotalPerSecond, 0);
+        let mut linear = unstake_account(1_000, true);
+
+        assert_eq!(linear.locked_amount(&staking_pool, 100).unwrap(), 1_000);
+        assert_eq!(linear.locked_amount(&staking_pool, 150).unwrap(), 500);
+        // Withdrawing the unlocked half leaves the locked half counted.
+        linear.withdrawn_amount = 500;
+        assert_eq!(linear.locked_amount(&staking_pool, 150).unwrap(), 500);
+        assert_eq!(linear.remaining_amount(&staking_pool).unwrap(), 500);
+        assert_eq!(linear.locked_amount(&staking_pool, 200).unwrap(), 0);
+
+        // Slashed unbonding funds count at their slashed value.
+        staking_pool.unbonding_scale = helper::SLASH_SCALE_BASE / 2;
+        assert_eq!(linear.locked_amount(&staking_pool, 150).unwrap(), 250);
+
+        // Nothing unlocks before the end without linear unbonding.
+        let at_end = unstake_account(1_000, false);
+        assert_eq!(at_end.locked_amount(&staking_pool, 199).unwrap(), 500);
+    }
+
+    #[test]
+    fn merge_keeps_rewards_and_weight() {
+        let mut staking_pool = pool(RewardAlgorithm::FixedTotalPerSecond, 0);
+        let mut position = stake_account(&mut staking_pool, 3_000);
+        let mut legacy = stake_account(&mut staking_pool, 1_000);
+        position.stake_timestamp = 100;
+        legacy.stake_timestamp = 500;
+
+        // 4 tokens per unit of weight since both were opened.
+        staking_pool.reward_per_share = 4 * helper::REWARD_PER_SHARE_BASE;
+        position.update_reward(&staking_pool).unwrap();
+        legacy.update_reward(&staking_pool).unwrap();
+        position.merge(&legacy, 1_000).unwrap();
+        position
+            .update_reward_debt(&mut staking_pool, 1_000)
+            .unwrap();
+
+        assert_eq!(position.amount, 4_000);
+        assert_eq!(position.weight, 4_000);
+        assert_eq!(position.reward, 16_000);
+        assert_eq!(position.stake_timestamp, 200);
+        assert_eq!(staking_pool.total_weight, 4_000);
+
+        // Nothing more is owed until the pool moves on.
+        position.update_reward(&staking_pool).unwrap();
+        assert_eq!(position.reward, 16_000);
+    }
+
+    #[test]
+    fn merge_rejects_two_locked_grants() {
+        let mut staking_pool = pool(RewardAlgorithm::FixedTotalPerSecond, 0);
+        let mut position = stake_account(&mut staking_pool, 1_000);
+        let mut legacy = stake_account(&mut staking_pool, 1_000);
+        for grant in [&mut position, &mut legacy] {
+            grant.vesting_amount = 500;
+            grant.vesting_cliff_timestamp = 200;
+            grant.vesting_end_timestamp = 300;
+        }
+
+        assert_eq!(
+            position.clone().merge(&legacy, 100).unwrap_err(),
+            error!(Errors::StakeAmountLocked)
+        );
+
+        // Once one grant fully vested, the other one is kept.
+        legacy.vesting_end_timestamp = 100;
+        legacy.vesting_cliff_timestamp = 100;
+        position.merge(&legacy, 100).unwrap();
+        assert_eq!(position.vesting_end_timestamp, 300);
+    }
+
+    fn change(
+        effective_timestamp: u64,
+        reward_rate: u64,
+        reward_algorithm: RewardAlgorithm,
+    ) -> ScheduledRewardChange {
+        ScheduledRewardChange {
+            effective_timestamp,
+            reward_rate,
+            reward_algorithm,
+        }
+    }
+
+    #[test]
+    fn schedule_applies_change_at_its_boundary() {
+        let mut staking_pool = pool(RewardAlgorithm::FixedTotalPerSecond, 10);
+        stake_account(&mut staking_pool, 1_000);
+        staking_pool.reward_schedule = vec![change(100, 20, RewardAlgorithm::FixedTotalPerSecond)];
+
+        staking_pool.update_pool_at(150).unwrap();
+
+        // 100 seconds at 10, then 50 at 20.
+        assert_eq!(staking_pool.total_owed_rewards, 2_000);
+        assert_eq!(staking_pool.reward_rate, 20);
+        assert!(staking_pool.reward_schedule.is_empty());
+        assert_eq!(staking_pool.last_reward_timestamp, 150);
+    }
+
+    #[test]
+    fn schedule_applies_several_changes_in_one_update() {
+        let mut staking_pool = pool(RewardAlgorithm::FixedTotalPerSecond, 10);
+        stake_account(&mut staking_pool, 1_000);
+        staking_pool.reward_schedule = vec![
+            change(100, 20, RewardAlgorithm::FixedTotalPerSecond),
+            change(200, 30, RewardAlgorithm::FixedTotalPerSecond),
+            change(300, 5, RewardAlgorithm::FixedTotalPerSecond),
+        ];
+
+        staking_pool.update_pool_at(250).unwrap();
+
+        // 100 seconds at 10, 100 at 20 and 50 at 30; the last change is due
+        // later.
+        assert_eq!(staking_pool.total_owed_rewards, 4_500);
+        assert_eq!(staking_pool.reward_rate, 30);
+        assert_eq!(
+            staking_pool.reward_schedule,
+            vec![change(300, 5, RewardAlgorithm::FixedTotalPerSecond)]
+        );
+    }
+
+    #[test]
+    fn schedule_applies_change_at_last_reward_timestamp() {
+        let mut staking_pool = pool(RewardAlgorithm::FixedTotalPerSecond, 10);
+        stake_account(&mut staking_pool, 1_000);
+        staking_pool.last_reward_timestamp = 100;
+        staking_pool.reward_schedule = vec![change(100, 20, RewardAlgorithm::FixedTotalPerSecond)];
+
+        staking_pool.update_pool_at(150).unwrap();
+
+        assert_eq!(staking_pool.total_owed_rewards, 1_000);
+        assert!(staking_pool.reward_schedule.is_empty());
+    }
+
+    #[test]
+    fn schedule_resets_remainder_on_algorithm_change() {
+        // 0.3 token a second over 1000 staked tokens.
+        let mut staking_pool = pool(RewardAlgorithm::FixedPerTokenPerSecond, 300_000_000);
+        stake_account(&mut staking_pool, 1_000);
+        staking_pool.reward_schedule = vec![
+            change(5, 200_000_000, RewardAlgorithm::FixedPerTokenPerSecond),
+            change(9, 1, RewardAlgorithm::FixedTotalPerSecond),
+        ];
+
+        // 1.5 tokens by the first change, whose remainder carries over.
+        staking_pool.update_pool_at(5).unwrap();
+        assert_eq!(staking_pool.total_owed_rewards, 1);
+        assert_eq!(
+            staking_pool.reward_remainder as u128,
+            helper::REWARD_CALC_BASE / 2
+        );
+
+        // 0.8 more by the second one: 1.3 is truncated to 1, and the 0.3 left
+        // is dropped as the new algorithm scales remainders differently.
+        staking_pool.update_pool_at(9).unwrap();
+        assert_eq!(staking_pool.total_owed_rewards, 2);
+        assert_eq!(staking_pool.reward_remainder, 0);
+        assert_eq!(
+            staking_pool.reward_algorithm,
+            RewardAlgorithm::FixedTotalPerSecond
+        );
+
+        staking_pool.update_pool_at(12).unwrap();
+        assert_eq!(staking_pool.total_owed_rewards, 5);
+    }
+
+    #[test]
+    fn loyalty_multiplier_ramps_up() {
+        let mut staking_pool = pool(RewardAlgorithm::FixedTotalPerSecond, 0);
+        // Disabled: always 1x.
+        assert_eq!(staking_pool.loyalty_multiplier(1_000_000), 10_000);
+
+        // Up to 3x over 1000 seconds.
+        staking_pool.max_loyalty_multiplier = 30_000;
+        staking_pool.loyalty_ramp_seconds = 1_000;
+        assert_eq!(staking_pool.loyalty_multiplier(0), 10_000);
+        assert_eq!(staking_pool.loyalty_multiplier(250), 15_000);
+        assert_eq!(staking_pool.loyalty_multiplier(999), 29_980);
+        assert_eq!(staking_pool.loyalty_multiplier(1_000), 30_000);
+        assert_eq!(staking_pool.loyalty_multiplier(u64::MAX), 30_000);
+
+        // A maximum below 1x is ignored rather than shrinking weights.
+        staking_pool.max_loyalty_multiplier = 5_000;
+        assert_eq!(staking_pool.loyalty_multiplier(1_000), 10_000);
+    }
+
+    #[test]
+    fn calc_weight_applies_multiplier() {
+        let mut staking_pool = pool(RewardAlgorithm::FixedTotalPerSecond, 0);
+        staking_pool.max_loyalty_multiplier = 30_000;
+        staking_pool.loyalty_ramp_seconds = 1_000;
+
+        assert_eq!(staking_pool.calc_weight(1_001, 0).unwrap(), 1_001);
+        assert_eq!(staking_pool.calc_weight(1_001, 250).unwrap(), 1_501);
+        assert_eq!(staking_pool.calc_weight(0, 1_000).unwrap(), 0);
+        assert_eq!(
+            staking_pool.calc_weight(u64::MAX, 1_000).unwrap_err(),
+            error!(Errors::CalculationFail)
+        );
+    }
+
+    #[test]
+    fn unlocked_amount_linear_and_at_end() {
+        let linear = unstake_account(1_000, true);
+        assert_eq!(linear.unlocked_amount(0), 0);
+        assert_eq!(linear.unlocked_amount(100), 0);
+        assert_eq!(linear.unlocked_amount(101), 10);
+        assert_eq!(linear.unlocked_amount(175), 750);
+        assert_eq!(linear.unlocked_amount(200), 1_000);
+        assert_eq!(linear.unlocked_amount(u64::MAX), 1_000);
+
+        let at_end = unstake_account(1_000, false);
+        assert_eq!(at_end.unlocked_amount(199), 0);
+        assert_eq!(at_end.unlocked_amount(200), 1_000);
+
+        // Accounts predating linear unbonding have no start timestamp.
+        let mut legacy = unstake_account(1_000, false);
+        legacy.unstake_timestamp = 0;
+        assert_eq!(legacy.unlocked_amount(150), 0);
+    }
+
+    #[test]
+    fn vesting_locked_amount() {
+        let mut staking_pool = pool(RewardAlgorithm::FixedTotalPerSecond, 0);
+        let mut grant = stake_account(&mut staking_pool, 1_500);
+        assert_eq!(grant.locked_amount(0), 0);
+
+        // 1000 units vesting from 100 to 300, with a cliff at 150.
+        grant.vesting_amount = 1_000;
+        grant.vesting_start_timestamp = 100;
+        grant.vesting_cliff_timestamp = 150;
+        grant.vesting_end_timestamp = 300;
+
+        assert_eq!(grant.locked_amount(0), 1_000);
+        assert_eq!(grant.locked_amount(149), 1_000);
+        // At the cliff, vesting catches up from the start.
+        assert_eq!(grant.locked_amount(150), 750);
+        assert_eq!(grant.locked_amount(200), 500);
+        assert_eq!(grant.locked_amount(299), 5);
+        assert_eq!(grant.locked_amount(300), 0);
+        assert_eq!(grant.locked_amount(u64::MAX), 0);
+    }
+
+    #[test]
+    fn slash_scale_conversions() {
+        let mut staking_pool = pool(RewardAlgorithm::FixedTotalPerSecond, 0);
+        let alice = stake_account(&mut staking_pool, 1_000);
+        let bob = stake_account(&mut staking_pool, 3);
+
+        // Never slashed: units are tokens.
+        assert_eq!(
+            staking_pool.effective_stake_scale(),
+            helper::SLASH_SCALE_BASE
+        );
+        assert_eq!(staking_pool.stake_to_tokens(1_000).unwrap(), 1_000);
+        assert_eq!(staking_pool.tokens_to_stake(1_000).unwrap(), 1_000);
+
+        // A quarter slashed.
+        staking_pool.stake_scale = helper::SLASH_SCALE_BASE * 3 / 4;
+        assert_eq!(staking_pool.stake_to_tokens(alice.amount).unwrap(), 750);
+        assert_eq!(staking_pool.stake_to_tokens(bob.amount).unwrap(), 2);
+        assert_eq!(staking_pool.total_stake_tokens().unwrap(), 752);
+        // New stake buys more units, rounded down.
+        assert_eq!(staking_pool.tokens_to_stake(750).unwrap(), 1_000);
+        assert_eq!(staking_pool.tokens_to_stake(1).unwrap(), 1);
+        // Positions round down, so they never add up to more than the pool.
+        assert!(
+            staking_pool.stake_to_tokens(alice.amount).unwrap()
+                + staking_pool.stake_to_tokens(bob.amount).unwrap()
+                <= staking_pool.total_stake_tokens().unwrap()
+        );
+    }
+
+    #[test]
+    fn unbonding_scale_conversions() {
+        let mut staking_pool = pool(RewardAlgorithm::FixedTotalPerSecond, 0);
+        let unstake_account = unstake_account(1_000, false);
+        let mut legacy = unstake_account.clone();
+        legacy.unbonding_scale = 0;
+
+        assert_eq!(
+            unstake_account.to_tokens(&staking_pool, 1_000).unwrap(),
+            1_000
+        );
+
+        // Unbonding funds halved after the account was created.
+        staking_pool.unbonding_scale = helper::SLASH_SCALE_BASE / 2;
+        assert_eq!(
+            unstake_account.to_tokens(&staking_pool, 1_000).unwrap(),
+            500
+        );
+        assert_eq!(
+            unstake_account.remaining_amount(&staking_pool).unwrap(),
+            500
+        );
+        // Accounts predating slashing are not subject to it.
+        assert_eq!(legacy.to_tokens(&staking_pool, 1_000).unwrap(), 1_000);
+
+        // Created after that slash, only later slashes apply.
+        let mut later = unstake_account.clone();
+        later.unbonding_scale = helper::SLASH_SCALE_BASE / 2;
+        staking_pool.unbonding_scale = helper::SLASH_SCALE_BASE / 4;
+        assert_eq!(later.to_tokens(&staking_pool, 1_000).unwrap(), 500);
+    }
+
+    #[test]
+    fn add_amount_averages_timestamps() {
+        let mut staking_pool = pool(RewardAlgorithm::FixedTotalPerSecond, 0);
+        let mut stake_account = stake_account(&mut staking_pool, 3_000);
+        stake_account.stake_timestamp = 100;
+        stake_account.loyalty_timestamp = 400;
+
+        stake_account.add_amount(1_000, 1_000).unwrap();
+        assert_eq!(stake_account.amount, 4_000);
+        assert_eq!(stake_account.stake_timestamp, 325);
+        assert_eq!(stake_account.loyalty_timestamp, 550);
+
+        // Dust barely moves an aged position.
+        stake_account.add_amount(1, 1_000_000).unwrap();
+        assert_eq!(stake_account.stake_timestamp, 574);
+    }
+
+    #[test]
+    fn reward_of_max_weight_at_large_reward_per_share() {
+        let mut staking_pool = pool(RewardAlgorithm::FixedTotalPerSecond, 0);
+        // 1e12 tokens per unit of weight.
+        staking_pool.reward_per_share = helper::REWARD_PER_SHARE_BASE * 1_000_000_000_000;
+        let expected = (u64::MAX as u128) * 1_000_000_000_000;
+
+        assert_eq!(staking_pool.calc_reward_debt(u64::MAX).unwrap(), expected);
+
+        let mut stake_account = stake_account(&mut staking_pool, u64::MAX);
+        stake_account.reward_debt = 0;
+        stake_account.update_reward(&staking_pool).unwrap();
+        assert_eq!(stake_account.reward, expected);
+
+        stake_account
+            .update_reward_debt(&mut staking_pool, 0)
+            .unwrap();
+        assert_eq!(stake_account.reward_debt, expected);
+    }
+
+    #[test]
+    fn migrate_keeps_legacy_reward_and_debt() {
+        // A pool and position from before rewards were widened, with
+        // `reward_per_share` still scaled by `REWARD_CALC_BASE`.
+        let mut staking_pool = pool(RewardAlgorithm::FixedTotalPerSecond, 0);
+        staking_pool.version = 0;
+        staking_pool.reward_per_share = 2_500_000_000_000;
+        let mut stake_account = stake_account(&mut staking_pool, 1_000);
+        stake_account.version = 0;
+        stake_account.reward = 0;
+        stake_account.reward_debt = 0;
+        stake_account.legacy_reward = 7;
+        stake_account.legacy_reward_debt = 2_500;
+
+        let mut migrated = stake_account.clone();
+        migrated.migrate();
+        assert_eq!(migrated.reward, 7);
+        assert_eq!(migrated.reward_debt, 2_500);
+        assert_eq!(migrated.legacy_reward, 0);
+        assert_eq!(migrated.legacy_reward_debt, 0);
+
+        // Nothing was distributed since, so nothing more is owed.
+        staking_pool.migrate().unwrap();
+        stake_account.update_reward(&staking_pool).unwrap();
+        assert_eq!(stake_account.reward, 7);
+    }
+
+    #[test]
+    fn reward_per_share_carries_remainder() {
+        let mut staking_pool = pool(RewardAlgorithm::FixedTotalPerSecond, 0);
+
+        // 1 token over a weight of 3 truncates, until the carried remainders
+        // add up to a whole unit again.
+        for _ in 0..3 {
+            (
+                staking_pool.reward_per_share,
+                staking_pool.reward_per_share_remainder,
+            ) = staking_pool.calc_reward_per_share(1, 3).unwrap();
+        }
+        assert_eq!(staking_pool.reward_per_share, helper::REWARD_PER_SHARE_BASE);
+        assert_eq!(staking_pool.reward_per_share_remainder, 0);
+    }
+
+    #[test]
+    fn new_reward_carries_remainder() {
+        // 0.3 token a second over 1000 staked tokens.
+        let mut per_second = pool(RewardAlgorithm::FixedPerTokenPerSecond, 300_000_000);
+        let mut at_once = pool(RewardAlgorithm::FixedPerTokenPerSecond, 300_000_000);
+        stake_account(&mut per_second, 1_000);
+        stake_account(&mut at_once, 1_000);
+
+        for time in 1..=10 {
+            per_second.update_pool_at(time).unwrap();
+        }
+        at_once.update_pool_at(10).unwrap();
+
+        assert_eq!(per_second.total_owed_rewards, 3);
+        assert_eq!(per_second.total_owed_rewards, at_once.total_owed_rewards);
+        assert_eq!(per_second.reward_remainder, at_once.reward_remainder);
+        assert_eq!(per_second.reward_per_share, at_once.reward_per_share);
+    }
+
+    #[test]
+    fn amount_at_empty_buffer_is_zero() {
+        let checkpoints = empty_checkpoints();
+
+        assert_eq!(checkpoints.amount_at(0).unwrap(), 0);
+        assert_eq!(checkpoints.amount_at(100).unwrap(), 0);
+    }
+
+    #[test]
+    fn amount_at_before_oldest_entry() {
+        let owner = Pubkey::new_unique();
+
+        // Opened by the first deposit: nothing was staked before.
+        let mut checkpoints = empty_checkpoints();
+        checkpoints.record(owner, 10, 0, 5);
+        assert_eq!(checkpoints.amount_at(9).unwrap(), 0);
+
+        // Opened on an existing balance, which predates the checkpoints.
+        let mut checkpoints = empty_checkpoints();
+        checkpoints.record(owner, 10, 3, 5);
+        assert_eq!(
+            checkpoints.amount_at(9).unwrap_err(),
+            error!(Errors::CheckpointNotFound)
+        );
+    }
+
+    #[test]
+    fn amount_at_exact_and_between_slots() {
+        let owner = Pubkey::new_unique();
+        let mut checkpoints = empty_checkpoints();
+        checkpoints.record(owner, 10, 0, 5);
+        checkpoints.record(owner, 20, 5, 8);
+        checkpoints.record(owner, 30, 8, 2);
+
+        assert_eq!(checkpoints.amount_at(10).unwrap(), 5);
+        assert_eq!(checkpoints.amount_at(19).unwrap(), 5);
+        assert_eq!(checkpoints.amount_at(20).unwrap(), 8);
+        assert_eq!(checkpoints.amount_at(30).unwrap(), 2);
+        assert_eq!(checkpoints.amount_at(1_000).unwrap(), 2);
+    }
+
+    #[test]
+    fn amount_at_after_wraparound() {
+        let owner = Pubkey::new_unique();
+        let mut checkpoints = empty_checkpoints();
+        let writes = helper::CHECKPOINTS_CAPACITY as u64 + 5;
+        for i in 1..=writes {
+            checkpoints.record(owner, i * 10, i - 1, i);
+        }
+        assert_eq!(checkpoints.len as usize, helper::CHECKPOINTS_CAPACITY);
+
+        // The oldest kept checkpoint is the 6th write.
+        assert_eq!(checkpoints.amount_at(60).unwrap(), 6);
+        assert_eq!(checkpoints.amount_at(65).unwrap(), 6);
+        assert_eq!(checkpoints.amount_at(writes * 10).unwrap(), writes);
+        assert_eq!(
+            checkpoints.amount_at(59).unwrap_err(),
+            error!(Errors::CheckpointNotFound)
+        );
+        assert_eq!(
+            checkpoints.amount_at(10).unwrap_err(),
+            error!(Errors::CheckpointNotFound)
+        );
+    }
+
+    #[test]
+    fn amount_at_keeps_last_write_of_a_slot() {
+        let owner = Pubkey::new_unique();
+        let mut checkpoints = empty_checkpoints();
+        checkpoints.record(owner, 10, 0, 5);
+        checkpoints.record(owner, 20, 5, 7);
+        checkpoints.record(owner, 20, 7, 9);
+        checkpoints.record(owner, 20, 9, 4);
+
+        assert_eq!(checkpoints.len, 2);
+        assert_eq!(checkpoints.amount_at(19).unwrap(), 5);
+        assert_eq!(checkpoints.amount_at(20).unwrap(), 4);
+    }
 }
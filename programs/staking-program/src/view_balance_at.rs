@@ -0,0 +1,15 @@
+use crate::BalanceCheckpoints;
+use anchor_lang::prelude::*;
+
+#[derive(Accounts)]
+pub struct GetBalanceAt<'info> {
+    pub checkpoints: Box<Account<'info, BalanceCheckpoints>>,
+}
+
+impl<'info> GetBalanceAt<'info> {
+    /// Stake of a user, or total stake of a pool, at the end of `slot`, in
+    /// stake units.
+    pub fn process(&self, slot: u64) -> Result<u64> {
+        self.checkpoints.amount_at(slot)
+    }
+}